@@ -0,0 +1,345 @@
+//! 远程控制守护进程：把 `TTHSDownloader` 暴露成一个可以跨进程操控的服务。
+//!
+//! [`DownloadManager`] 监听 TCP（或 Unix domain socket），按换行分隔接收 JSON 请求
+//! （`start`/`pause`/`resume`/`stop`/`list`/`subscribe`），并把对应下载的
+//! [`DownloadEventMsg`] 以 JSON 行的形式持续推送给订阅了该连接的客户端。
+//! 握手阶段交换 `protocol_version`，版本不匹配的客户端会被直接拒绝。
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+
+use crate::downloader::{DownloadOptions, TTHSDownloader};
+use crate::event::DownloadEventMsg;
+
+/// 协议版本：客户端握手时携带，daemon 发现不匹配会拒绝后续请求
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    Hello { protocol_version: u32 },
+    Start { urls: Vec<String>, save_paths: Vec<String>, #[serde(default)] opts: DownloadOptions },
+    Pause { id: i32 },
+    Resume { id: i32 },
+    Stop { id: i32 },
+    List,
+    Subscribe { id: i32 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonResponse {
+    Hello { protocol_version: u32, ok: bool },
+    Started { id: i32 },
+    Ack,
+    Error { message: String },
+    List { ids: Vec<i32> },
+    Event { id: i32, msg: DownloadEventMsg },
+}
+
+type SubscriberMap = Arc<Mutex<HashMap<i32, Vec<mpsc::UnboundedSender<DownloadEventMsg>>>>>;
+
+/// 守护进程：拥有一个 `TTHSDownloader`，接受多个客户端连接发号施令
+pub struct DownloadManager {
+    downloader: TTHSDownloader,
+    /// downloader_id → 已订阅该下载事件的客户端 sender 列表
+    subscribers: SubscriberMap,
+}
+
+impl DownloadManager {
+    pub fn new(downloader: TTHSDownloader) -> Arc<Self> {
+        Arc::new(DownloadManager {
+            downloader,
+            subscribers: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// 监听 TCP 地址，接受连接直到出错
+    pub async fn serve_tcp(self: &Arc<Self>, addr: impl ToSocketAddrs) -> std::io::Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let this = self.clone();
+            tokio::spawn(async move { this.handle_connection(stream).await });
+        }
+    }
+
+    /// 监听 Unix domain socket，接受连接直到出错
+    #[cfg(unix)]
+    pub async fn serve_unix(self: &Arc<Self>, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(path)?;
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let this = self.clone();
+            tokio::spawn(async move { this.handle_connection(stream).await });
+        }
+    }
+
+    async fn handle_connection<S>(self: Arc<Self>, stream: S)
+    where
+        S: tokio::io::AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        // 和 subscription 的接收端一起维护，回放事件时用它还原出真正的 downloader id
+        let mut subscribed_id: Option<i32> = None;
+        let mut subscription: Option<mpsc::UnboundedReceiver<DownloadEventMsg>> = None;
+
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let line = match line {
+                        Ok(Some(line)) => line,
+                        _ => break,
+                    };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+
+                    let req: DaemonRequest = match serde_json::from_str(&line) {
+                        Ok(req) => req,
+                        Err(e) => {
+                            let resp = DaemonResponse::Error { message: format!("解析请求失败: {e}") };
+                            if Self::write_response(&mut writer, &resp).await.is_err() { break; }
+                            continue;
+                        }
+                    };
+
+                    match req {
+                        DaemonRequest::Hello { protocol_version } => {
+                            let ok = protocol_version == PROTOCOL_VERSION;
+                            let resp = DaemonResponse::Hello { protocol_version: PROTOCOL_VERSION, ok };
+                            if Self::write_response(&mut writer, &resp).await.is_err() || !ok {
+                                break;
+                            }
+                        }
+                        DaemonRequest::Subscribe { id } => {
+                            subscribed_id = Some(id);
+                            subscription = Some(self.subscribe(id));
+                            if Self::write_response(&mut writer, &DaemonResponse::Ack).await.is_err() {
+                                break;
+                            }
+                        }
+                        other => {
+                            let resp = self.dispatch(other);
+                            if Self::write_response(&mut writer, &resp).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+                Some(msg) = async {
+                    match subscription.as_mut() {
+                        Some(rx) => rx.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    // subscription 非 None 时 subscribed_id 必然也已经写入，不会退回 0
+                    let resp = DaemonResponse::Event { id: subscribed_id.unwrap_or(0), msg };
+                    if Self::write_response(&mut writer, &resp).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn dispatch(&self, req: DaemonRequest) -> DaemonResponse {
+        match req {
+            DaemonRequest::Start { urls, save_paths, opts } => match self.start(urls, save_paths, opts) {
+                Ok(id) => DaemonResponse::Started { id },
+                Err(e) => DaemonResponse::Error { message: e.to_string() },
+            },
+            DaemonRequest::Pause { id } => self.bool_response(self.downloader.pause_download(id)),
+            DaemonRequest::Resume { id } => self.bool_response(self.downloader.resume_download(id)),
+            DaemonRequest::Stop { id } => {
+                let ok = self.downloader.stop_download(id);
+                self.subscribers.lock().unwrap().remove(&id);
+                self.bool_response(ok)
+            }
+            DaemonRequest::List => {
+                let ids = self.subscribers.lock().unwrap().keys().copied().collect();
+                DaemonResponse::List { ids }
+            }
+            DaemonRequest::Hello { .. } | DaemonRequest::Subscribe { .. } => {
+                DaemonResponse::Error { message: "握手/订阅请求需要在连接层处理".to_string() }
+            }
+        }
+    }
+
+    fn bool_response(&self, ok: bool) -> DaemonResponse {
+        if ok {
+            DaemonResponse::Ack
+        } else {
+            DaemonResponse::Error { message: "操作失败".to_string() }
+        }
+    }
+
+    /// 启动一批下载，并起一个转发任务把事件广播给所有已订阅该 id 的客户端
+    fn start(
+        &self,
+        urls: Vec<String>,
+        save_paths: Vec<String>,
+        opts: DownloadOptions,
+    ) -> Result<i32, Box<dyn std::error::Error>> {
+        let (id, mut rx) = self.downloader.start_download(urls, save_paths, opts)?;
+        self.subscribers.lock().unwrap().entry(id).or_default();
+
+        let subscribers = self.subscribers.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                let mut subs = subscribers.lock().unwrap();
+                if let Some(list) = subs.get_mut(&id) {
+                    list.retain(|tx| tx.send(msg.clone()).is_ok());
+                }
+            }
+            subscribers.lock().unwrap().remove(&id);
+        });
+
+        Ok(id)
+    }
+
+    fn subscribe(&self, id: i32) -> mpsc::UnboundedReceiver<DownloadEventMsg> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap().entry(id).or_default().push(tx);
+        rx
+    }
+
+    async fn write_response<W>(writer: &mut W, resp: &DaemonResponse) -> std::io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut line = serde_json::to_string(resp).unwrap_or_else(|_| "{}".to_string());
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await
+    }
+}
+
+type ClientConn = (BufReader<OwnedReadHalf>, OwnedWriteHalf);
+
+/// 瘦客户端：包一层 socket，暴露和进程内路径一致的 async API
+pub struct DaemonClient {
+    conn: Mutex<Option<ClientConn>>,
+}
+
+impl DaemonClient {
+    /// 连接 daemon 并完成 `protocol_version` 握手
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self, Box<dyn std::error::Error>> {
+        let stream = TcpStream::connect(addr).await?;
+        let (read_half, mut write_half) = stream.into_split();
+        let mut reader = BufReader::new(read_half);
+
+        Self::write_request(&mut write_half, &DaemonRequest::Hello { protocol_version: PROTOCOL_VERSION }).await?;
+        match Self::read_response(&mut reader).await? {
+            DaemonResponse::Hello { ok: true, .. } => {
+                Ok(DaemonClient { conn: Mutex::new(Some((reader, write_half))) })
+            }
+            DaemonResponse::Hello { ok: false, protocol_version } => {
+                Err(format!("协议版本不匹配: daemon={protocol_version}, client={PROTOCOL_VERSION}").into())
+            }
+            other => Err(format!("握手时收到意外响应: {other:?}").into()),
+        }
+    }
+
+    pub async fn start_download(
+        &self,
+        urls: Vec<String>,
+        save_paths: Vec<String>,
+        opts: DownloadOptions,
+    ) -> Result<i32, Box<dyn std::error::Error>> {
+        match self.request(&DaemonRequest::Start { urls, save_paths, opts }).await? {
+            DaemonResponse::Started { id } => Ok(id),
+            DaemonResponse::Error { message } => Err(message.into()),
+            other => Err(format!("意外响应: {other:?}").into()),
+        }
+    }
+
+    pub async fn pause(&self, id: i32) -> Result<(), Box<dyn std::error::Error>> {
+        self.ack(&DaemonRequest::Pause { id }).await
+    }
+
+    pub async fn resume(&self, id: i32) -> Result<(), Box<dyn std::error::Error>> {
+        self.ack(&DaemonRequest::Resume { id }).await
+    }
+
+    pub async fn stop(&self, id: i32) -> Result<(), Box<dyn std::error::Error>> {
+        self.ack(&DaemonRequest::Stop { id }).await
+    }
+
+    pub async fn list(&self) -> Result<Vec<i32>, Box<dyn std::error::Error>> {
+        match self.request(&DaemonRequest::List).await? {
+            DaemonResponse::List { ids } => Ok(ids),
+            DaemonResponse::Error { message } => Err(message.into()),
+            other => Err(format!("意外响应: {other:?}").into()),
+        }
+    }
+
+    /// 订阅事件后，本连接只能用于接收事件流，不能再发起其它请求（消费掉底层 socket）
+    pub async fn subscribe(&self, id: i32) -> Result<mpsc::UnboundedReceiver<DownloadEventMsg>, Box<dyn std::error::Error>> {
+        match self.request(&DaemonRequest::Subscribe { id }).await? {
+            DaemonResponse::Ack => {}
+            DaemonResponse::Error { message } => return Err(message.into()),
+            other => return Err(format!("意外响应: {other:?}").into()),
+        }
+
+        let (mut reader, mut writer) = self.conn.lock().unwrap().take().ok_or("连接已被其它订阅消费")?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let _keep_alive = &mut writer;
+            loop {
+                match Self::read_response(&mut reader).await {
+                    Ok(DaemonResponse::Event { msg, .. }) => {
+                        if tx.send(msg).is_err() {
+                            break;
+                        }
+                    }
+                    _ => break,
+                }
+            }
+        });
+        Ok(rx)
+    }
+
+    async fn ack(&self, req: &DaemonRequest) -> Result<(), Box<dyn std::error::Error>> {
+        match self.request(req).await? {
+            DaemonResponse::Ack => Ok(()),
+            DaemonResponse::Error { message } => Err(message.into()),
+            other => Err(format!("意外响应: {other:?}").into()),
+        }
+    }
+
+    /// 取出连接发起一次请求-响应往返，再把连接放回去（订阅期间连接被 `subscribe` 占用，此时调用会失败）
+    async fn request(&self, req: &DaemonRequest) -> Result<DaemonResponse, Box<dyn std::error::Error>> {
+        let (mut reader, mut writer) = self.conn.lock().unwrap().take().ok_or("连接已被订阅消费")?;
+        Self::write_request(&mut writer, req).await?;
+        let resp = Self::read_response(&mut reader).await?;
+        *self.conn.lock().unwrap() = Some((reader, writer));
+        Ok(resp)
+    }
+
+    async fn write_request(writer: &mut OwnedWriteHalf, req: &DaemonRequest) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(req).unwrap_or_else(|_| "{}".to_string());
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await
+    }
+
+    /// 从持久化的 `BufReader` 中读一行；读空行（EOF）说明 daemon 关闭了连接
+    async fn read_response(reader: &mut BufReader<OwnedReadHalf>) -> Result<DaemonResponse, Box<dyn std::error::Error>> {
+        let mut line = String::new();
+        let n = reader.read_line(&mut line).await?;
+        if n == 0 {
+            return Err("daemon 关闭了连接".into());
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
@@ -7,23 +7,49 @@ use std::ffi::{CString, c_void};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::time::Instant;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
 use crate::ffi::TthsdRaw;
-use crate::event::{DownloadEvent, DownloadEventMsg, EventData};
+use crate::event::{DownloadEvent, DownloadEventKind, DownloadEventMsg, EventData};
+use crate::qos::QosController;
+use crate::scheduler::Scheduler;
+use crate::snapshot::{DownloaderSnapshot, SnapshotFormat};
 
 /// 任务描述
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DownloadTask {
     pub url: String,
     pub save_path: String,
     pub show_name: String,
     pub id: String,
+    /// 调度优先级，数值越大越先被 [`crate::scheduler::PriorityScheduler`] 取出；默认 0
+    #[serde(default)]
+    pub priority: i32,
+}
+
+impl DownloadTask {
+    pub fn new(url: impl Into<String>, save_path: impl Into<String>) -> Self {
+        let url = url.into();
+        let show_name = url.rsplit('/').next().unwrap_or("").split('?').next().unwrap_or("").to_string();
+        DownloadTask {
+            url,
+            save_path: save_path.into(),
+            show_name,
+            id: Uuid::new_v4().to_string(),
+            priority: 0,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
 }
 
 /// start_download / get_downloader 的可选参数
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct DownloadOptions {
     pub thread_count: Option<usize>,
     pub chunk_size_mb: Option<usize>,
@@ -46,6 +72,20 @@ fn sender_map() -> &'static Mutex<SenderMap> {
     SENDER_MAP.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
+/// 任务 id（UUID）→ 所属 downloader_id 的映射
+///
+/// `build_tasks_json` 在生成任务时分配任务 id，调用成功后由
+/// `start_download`/`get_downloader` 把这些 id 登记到对应的 downloader_id 上，
+/// 这样 `global_c_callback` 收到任务级事件（`DownloadEvent.id` 是 UUID）时，
+/// 就能反查出应该投递给哪个 channel。
+type TaskOwnerMap = HashMap<String, i32>;
+
+static TASK_OWNER_MAP: std::sync::OnceLock<Mutex<TaskOwnerMap>> = std::sync::OnceLock::new();
+
+fn task_owner_map() -> &'static Mutex<TaskOwnerMap> {
+    TASK_OWNER_MAP.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
 /// 注册一个 id → sender 映射，并返回对应的 receiver
 fn register_channel(id: i32) -> mpsc::UnboundedReceiver<DownloadEventMsg> {
     let (tx, rx) = mpsc::unbounded_channel();
@@ -55,12 +95,113 @@ fn register_channel(id: i32) -> mpsc::UnboundedReceiver<DownloadEventMsg> {
 
 fn unregister_channel(id: i32) {
     sender_map().lock().unwrap().remove(&id);
+    task_owner_map().lock().unwrap().retain(|_, owner| *owner != id);
+    if let Some(tasks) = tasks_map().lock().unwrap().remove(&id) {
+        let mut progress = progress_map().lock().unwrap();
+        let mut speeds = speed_map().lock().unwrap();
+        for task in &tasks {
+            progress.remove(&task.id);
+            speeds.remove(&task.id);
+        }
+    }
+    options_map().lock().unwrap().remove(&id);
+}
+
+/// 把一批任务 id 登记到 downloader_id 上（`start_download`/`get_downloader` 调用成功后调用）
+fn register_task_owners(task_ids: &[String], downloader_id: i32) {
+    let mut map = task_owner_map().lock().unwrap();
+    for task_id in task_ids {
+        map.insert(task_id.clone(), downloader_id);
+    }
+}
+
+// ------------------------------------------------------------------
+// 快照支持：记录每个 downloader_id 提交过的任务/参数，以及每个任务目前已下载的字节数，
+// 供 `save_snapshot`/`resume_from_snapshot` 在进程重启后恢复现场。
+// ------------------------------------------------------------------
+type TasksMap = HashMap<i32, Vec<DownloadTask>>;
+type OptionsMap = HashMap<i32, DownloadOptions>;
+type ProgressMap = HashMap<String, u64>;
+
+static TASKS_MAP: std::sync::OnceLock<Mutex<TasksMap>> = std::sync::OnceLock::new();
+static OPTIONS_MAP: std::sync::OnceLock<Mutex<OptionsMap>> = std::sync::OnceLock::new();
+static PROGRESS_MAP: std::sync::OnceLock<Mutex<ProgressMap>> = std::sync::OnceLock::new();
+
+fn tasks_map() -> &'static Mutex<TasksMap> {
+    TASKS_MAP.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn options_map() -> &'static Mutex<OptionsMap> {
+    OPTIONS_MAP.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn progress_map() -> &'static Mutex<ProgressMap> {
+    PROGRESS_MAP.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn register_tasks(downloader_id: i32, tasks: Vec<DownloadTask>, opts: DownloadOptions) {
+    tasks_map().lock().unwrap().insert(downloader_id, tasks);
+    options_map().lock().unwrap().insert(downloader_id, opts);
+}
+
+// ------------------------------------------------------------------
+// 速度估算：一个 downloader 可以同时跑多个任务（start_download 接收 Vec<url>），
+// 它们的 update 事件会交替路由到同一个 downloader_id，所以按 task id（`event.id`）
+// 记录上一次 progress 回调的时间戳与字节数，而不是按 downloader_id 混在一起记——
+// 否则交替到达的两个任务的字节数相减会得到 0 或负数饱和成 0，speed/ETA 全部失真。
+// 用指数滑动平均（EMA，alpha≈0.3）平滑瞬时速度，供 `DownloadEventKind::Progress` 使用
+// ------------------------------------------------------------------
+const SPEED_EMA_ALPHA: f64 = 0.3;
+
+struct SpeedSample {
+    last_instant: Instant,
+    last_downloaded_bytes: u64,
+    avg_speed_bps: f64,
+}
+
+type SpeedMap = HashMap<String, SpeedSample>;
+
+static SPEED_MAP: std::sync::OnceLock<Mutex<SpeedMap>> = std::sync::OnceLock::new();
+
+fn speed_map() -> &'static Mutex<SpeedMap> {
+    SPEED_MAP.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 根据最新的 `downloaded_bytes` 更新 task_id 的速度估算，返回 `(瞬时速度, 平均速度)`（字节/秒）
+fn track_speed(task_id: &str, downloaded_bytes: u64) -> (u64, u64) {
+    let now = Instant::now();
+    let mut map = speed_map().lock().unwrap();
+    let sample = map.entry(task_id.to_string()).or_insert_with(|| SpeedSample {
+        last_instant: now,
+        last_downloaded_bytes: downloaded_bytes,
+        avg_speed_bps: 0.0,
+    });
+
+    let elapsed = now.duration_since(sample.last_instant).as_secs_f64();
+    let instant_speed_bps = if elapsed > 0.0 {
+        downloaded_bytes.saturating_sub(sample.last_downloaded_bytes) as f64 / elapsed
+    } else {
+        0.0
+    };
+
+    sample.avg_speed_bps = if sample.avg_speed_bps == 0.0 {
+        instant_speed_bps
+    } else {
+        SPEED_EMA_ALPHA * instant_speed_bps + (1.0 - SPEED_EMA_ALPHA) * sample.avg_speed_bps
+    };
+    sample.last_instant = now;
+    sample.last_downloaded_bytes = downloaded_bytes;
+
+    (instant_speed_bps.round() as u64, sample.avg_speed_bps.round() as u64)
 }
 
 /// TTHSD C 回调 → Rust 的静态转发函数
 ///
-/// 因为 C 回调不携带 userdata，所以通过全局 sender_map 广播给所有 channel。
-/// 实际工程中回调携带 ID 字段，通过 event.ID 做精确路由。  
+/// 因为 C 回调不携带 userdata，无法直接知道事件属于哪个 downloader_id，
+/// 所以按优先级解析路由目标：
+/// 1. `DownloadEvent.id` 本身就是 downloader_id（数字字符串）；
+/// 2. `DownloadEvent.id` 是任务 id（UUID），通过 `task_owner_map` 反查所属 downloader_id；
+/// 3. 都匹配不到时（例如全局级别的 `start`/`end` 事件，`id` 为空），退化为广播。
 extern "C" fn global_c_callback(
     event_ptr: *const std::ffi::c_char,
     data_ptr: *const std::ffi::c_char,
@@ -80,12 +221,40 @@ extern "C" fn global_c_callback(
         Ok(e) => e,
         Err(_) => return,
     };
-    let data: EventData = serde_json::from_str(data_str).unwrap_or_default();
+    let mut data: EventData = serde_json::from_str(data_str).unwrap_or_default();
+
+    let target_id = event.id.parse::<i32>().ok().or_else(|| {
+        task_owner_map().lock().unwrap().get(&event.id).copied()
+    });
+
+    // 进度事件：顺带算出瞬时/平均速度，塞进 data，供 DownloadEventMsg::kind() 解析出 ProgressInfo。
+    // 按 task id（event.id）而不是 downloader_id 记录，一个 downloader 下多个任务的进度
+    // 互不干扰；没有 task id 的全局级别事件（event.id 为空）没法归属到某个任务，不计算速度
+    if event.event_type == "update" && !event.id.is_empty() {
+        if let Some(downloaded) = data.get("Downloaded").and_then(|v| v.as_u64()) {
+            let (instant_speed_bps, avg_speed_bps) = track_speed(&event.id, downloaded);
+            data.insert("InstantSpeedBps".to_string(), serde_json::Value::from(instant_speed_bps));
+            data.insert("AvgSpeedBps".to_string(), serde_json::Value::from(avg_speed_bps));
+        }
+        // 记录单个任务目前下载到的字节数，供 save_snapshot 落盘
+        if !event.id.is_empty() {
+            if let Some(downloaded) = data.get("Downloaded").and_then(|v| v.as_u64()) {
+                progress_map().lock().unwrap().insert(event.id.clone(), downloaded);
+            }
+        }
+    }
 
     let msg = DownloadEventMsg { event: event.clone(), data };
 
-    // 广播到所有注册的 channel（通常同一时刻只有少量 channel）
     let map = sender_map().lock().unwrap();
+    if let Some(downloader_id) = target_id {
+        if let Some(sender) = map.get(&downloader_id) {
+            let _ = sender.send(msg);
+            return;
+        }
+    }
+
+    // 没有匹配到任何 downloader（通常是全局事件，或尚未登记的任务 id）：退化为广播
     for sender in map.values() {
         let _ = sender.send(msg.clone());
     }
@@ -95,16 +264,31 @@ extern "C" fn global_c_callback(
 // TTHSDownloader
 // ------------------------------------------------------------------
 
+/// 底层实现：优先使用原生 TTHSD 动态库，`fallback` feature 打开时，
+/// 若动态库加载失败则退化到纯 Rust 的 [`crate::fallback::FallbackDownloader`]。
+#[derive(Clone)]
+enum Backend {
+    Native(Arc<TthsdRaw>),
+    #[cfg(feature = "fallback")]
+    Fallback(Arc<crate::fallback::FallbackDownloader>),
+}
+
 /// TTHSD 高速下载器安全 Rust 封装
 ///
 /// 通过 `libloading` 动态加载 TTHSD 动态库（.dll/.so/.dylib），
 /// 提供安全 API 并返回异步 `mpsc::UnboundedReceiver<DownloadEventMsg>` 事件流。
+///
+/// 启用 `fallback` feature 时，若找不到动态库会自动退化到纯 Rust 的 HTTP 分段下载后端，
+/// 两条路径暴露完全相同的 `start_download`/`pause_download`/`resume_download`/`stop_download` API。
+#[derive(Clone)]
 pub struct TTHSDownloader {
-    raw: Arc<TthsdRaw>,
+    backend: Backend,
+    /// 通过 [`TTHSDownloader::submit_batch`] 批量提交时的最大并发下载数
+    max_concurrent: usize,
 }
 
 impl TTHSDownloader {
-    /// 加载 TTHSD 动态库
+    /// 加载 TTHSD 动态库；找不到时按 `fallback` feature 决定是否退化为纯 Rust 后端
     ///
     /// @param lib_path 动态库路径（`None` 则在当前目录搜索默认名称）
     pub fn load(lib_path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
@@ -112,21 +296,38 @@ impl TTHSDownloader {
             Some(p) => p.to_path_buf(),
             None => PathBuf::from(TthsdRaw::default_lib_name()),
         };
-        let raw = TthsdRaw::load(&path)?;
-        Ok(Self { raw: Arc::new(raw) })
+        let backend = match TthsdRaw::load(&path) {
+            Ok(raw) => Backend::Native(Arc::new(raw)),
+            #[cfg(feature = "fallback")]
+            Err(e) => {
+                eprintln!("警告: 加载原生 TTHSD 动态库失败 ({}), 回退到纯 Rust 下载后端", e);
+                Backend::Fallback(Arc::new(crate::fallback::FallbackDownloader::new()))
+            }
+            #[cfg(not(feature = "fallback"))]
+            Err(e) => return Err(e),
+        };
+        Ok(Self { backend, max_concurrent: usize::MAX })
+    }
+
+    /// 设置 [`TTHSDownloader::submit_batch`] 的最大并发下载数（默认不限）
+    pub fn with_max_concurrency(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = max_concurrent.max(1);
+        self
     }
 
     // ------------------------------------------------------------------
     // 私有工具
     // ------------------------------------------------------------------
 
+    /// 构建任务 JSON，同时返回每个任务分配到的 id（用于登记 `task_owner_map`）
     fn build_tasks_json(
         urls: &[String],
         save_paths: &[String],
         show_names: Option<&[String]>,
         ids: Option<&[String]>,
-    ) -> Result<CString, Box<dyn std::error::Error>> {
+    ) -> Result<(CString, Vec<String>), Box<dyn std::error::Error>> {
         assert_eq!(urls.len(), save_paths.len(), "urls 与 save_paths 长度不一致");
+        let mut task_ids = Vec::with_capacity(urls.len());
         let tasks: Vec<serde_json::Value> = urls.iter().enumerate().map(|(i, url)| {
             let show_name = show_names
                 .and_then(|s| s.get(i))
@@ -138,6 +339,7 @@ impl TTHSDownloader {
                 .and_then(|s| s.get(i))
                 .map(|s| s.clone())
                 .unwrap_or_else(|| Uuid::new_v4().to_string());
+            task_ids.push(id.clone());
             serde_json::json!({
                 "url":       url,
                 "save_path": save_paths[i],
@@ -145,7 +347,23 @@ impl TTHSDownloader {
                 "id":        id,
             })
         }).collect();
-        Ok(CString::new(serde_json::to_string(&tasks)?)?)
+        Ok((CString::new(serde_json::to_string(&tasks)?)?, task_ids))
+    }
+
+    /// 把 `urls`/`save_paths`/`task_ids` 拼回 `DownloadTask` 列表，供 `register_tasks` 存档使用
+    fn tasks_from(urls: &[String], save_paths: &[String], task_ids: &[String]) -> Vec<DownloadTask> {
+        urls.iter().zip(save_paths.iter()).zip(task_ids.iter())
+            .map(|((url, save_path), id)| {
+                let show_name = url.rsplit('/').next().unwrap_or("").split('?').next().unwrap_or("").to_string();
+                DownloadTask {
+                    url: url.clone(),
+                    save_path: save_path.clone(),
+                    show_name,
+                    id: id.clone(),
+                    priority: 0,
+                }
+            })
+            .collect()
     }
 
     // ------------------------------------------------------------------
@@ -159,7 +377,13 @@ impl TTHSDownloader {
         save_paths: Vec<String>,
         opts: DownloadOptions,
     ) -> Result<(i32, mpsc::UnboundedReceiver<DownloadEventMsg>), Box<dyn std::error::Error>> {
-        let tasks_json = Self::build_tasks_json(&urls, &save_paths, None, None)?;
+        let raw = match &self.backend {
+            Backend::Native(raw) => raw,
+            #[cfg(feature = "fallback")]
+            Backend::Fallback(fb) => return fb.start_download(urls, save_paths, opts),
+        };
+
+        let (tasks_json, task_ids) = Self::build_tasks_json(&urls, &save_paths, None, None)?;
         let thread_count = opts.thread_count.unwrap_or(64) as i32;
         let chunk_size_mb = opts.chunk_size_mb.unwrap_or(10) as i32;
         let ua = opts.user_agent.as_deref().map(CString::new).transpose()?;
@@ -169,7 +393,7 @@ impl TTHSDownloader {
         let is_multiple_val: Option<bool> = opts.is_multiple;
 
         let id = unsafe {
-            (self.raw.fn_start_download)(
+            (raw.fn_start_download)(
                 tasks_json.as_ptr(),
                 urls.len() as i32,
                 thread_count,
@@ -188,6 +412,8 @@ impl TTHSDownloader {
         }
 
         let rx = register_channel(id);
+        register_task_owners(&task_ids, id);
+        register_tasks(id, Self::tasks_from(&urls, &save_paths, &task_ids), opts);
         Ok((id, rx))
     }
 
@@ -198,7 +424,13 @@ impl TTHSDownloader {
         save_paths: Vec<String>,
         opts: DownloadOptions,
     ) -> Result<(i32, mpsc::UnboundedReceiver<DownloadEventMsg>), Box<dyn std::error::Error>> {
-        let tasks_json = Self::build_tasks_json(&urls, &save_paths, None, None)?;
+        let raw = match &self.backend {
+            Backend::Native(raw) => raw,
+            #[cfg(feature = "fallback")]
+            Backend::Fallback(fb) => return fb.get_downloader(urls, save_paths, opts),
+        };
+
+        let (tasks_json, task_ids) = Self::build_tasks_json(&urls, &save_paths, None, None)?;
         let thread_count = opts.thread_count.unwrap_or(64) as i32;
         let chunk_size_mb = opts.chunk_size_mb.unwrap_or(10) as i32;
         let ua = opts.user_agent.as_deref().map(CString::new).transpose()?;
@@ -206,7 +438,7 @@ impl TTHSDownloader {
         let use_socket_val = opts.use_socket;
 
         let id = unsafe {
-            (self.raw.fn_get_downloader)(
+            (raw.fn_get_downloader)(
                 tasks_json.as_ptr(),
                 urls.len() as i32,
                 thread_count,
@@ -224,28 +456,173 @@ impl TTHSDownloader {
         }
 
         let rx = register_channel(id);
+        register_task_owners(&task_ids, id);
+        register_tasks(id, Self::tasks_from(&urls, &save_paths, &task_ids), opts);
         Ok((id, rx))
     }
 
     pub fn start_download_by_id(&self, id: i32) -> bool {
-        unsafe { (self.raw.fn_start_download_id)(id) == 0 }
+        match &self.backend {
+            Backend::Native(raw) => unsafe { (raw.fn_start_download_id)(id) == 0 },
+            #[cfg(feature = "fallback")]
+            Backend::Fallback(fb) => fb.start_download_by_id(id),
+        }
     }
 
     pub fn start_multiple_downloads_by_id(&self, id: i32) -> bool {
-        unsafe { (self.raw.fn_start_multiple_downloads_id)(id) == 0 }
+        match &self.backend {
+            Backend::Native(raw) => unsafe { (raw.fn_start_multiple_downloads_id)(id) == 0 },
+            #[cfg(feature = "fallback")]
+            Backend::Fallback(fb) => fb.start_multiple_downloads_by_id(id),
+        }
     }
 
     pub fn pause_download(&self, id: i32) -> bool {
-        unsafe { (self.raw.fn_pause_download)(id) == 0 }
+        match &self.backend {
+            Backend::Native(raw) => unsafe { (raw.fn_pause_download)(id) == 0 },
+            #[cfg(feature = "fallback")]
+            Backend::Fallback(fb) => fb.pause_download(id),
+        }
     }
 
     pub fn resume_download(&self, id: i32) -> bool {
-        unsafe { (self.raw.fn_resume_download)(id) == 0 }
+        match &self.backend {
+            Backend::Native(raw) => unsafe { (raw.fn_resume_download)(id) == 0 },
+            #[cfg(feature = "fallback")]
+            Backend::Fallback(fb) => fb.resume_download(id),
+        }
     }
 
     pub fn stop_download(&self, id: i32) -> bool {
-        let ret = unsafe { (self.raw.fn_stop_download)(id) == 0 };
+        let ret = match &self.backend {
+            Backend::Native(raw) => unsafe { (raw.fn_stop_download)(id) == 0 },
+            #[cfg(feature = "fallback")]
+            Backend::Fallback(fb) => fb.stop_download(id),
+        };
         unregister_channel(id);
         ret
     }
+
+    /// 批量提交任务：调用方把任务塞进任意 [`Scheduler`]（`FifoScheduler`/`PriorityScheduler`），
+    /// 本方法在后台驱动调度器按 `max_concurrent` 取任务派发给 `start_download`，
+    /// 一个任务完成（`Completed`/`Failed`）才会腾出一个并发槽位启动下一个，
+    /// 所有任务的事件被汇总转发到返回的单个 receiver 上。
+    ///
+    /// 传入 `qos` 时，每个任务一启动就会登记到 [`QosController`]，随后收到的每条事件
+    /// 都会喂给它，由它按总速度预算/metered 标志决定是否暂停/恢复这些已经在跑的下载，
+    /// 和 `scheduler` 的"接下来启动谁"互不干扰、协同工作。
+    pub fn submit_batch<S>(
+        self: Arc<Self>,
+        mut scheduler: S,
+        opts: DownloadOptions,
+        qos: Option<Arc<QosController>>,
+    ) -> mpsc::UnboundedReceiver<DownloadEventMsg>
+    where
+        S: Scheduler<DownloadTask> + Send + 'static,
+    {
+        let (out_tx, out_rx) = mpsc::unbounded_channel();
+        let max_concurrent = self.max_concurrent.max(1);
+
+        tokio::spawn(async move {
+            let (slot_tx, mut slot_rx) = mpsc::unbounded_channel::<()>();
+            let mut running = 0usize;
+
+            loop {
+                while running < max_concurrent {
+                    let Some(task) = scheduler.pop() else { break };
+                    running += 1;
+
+                    let this = self.clone();
+                    let out_tx = out_tx.clone();
+                    let slot_tx = slot_tx.clone();
+                    let opts = opts.clone();
+                    let qos = qos.clone();
+
+                    tokio::spawn(async move {
+                        match this.start_download(vec![task.url.clone()], vec![task.save_path.clone()], opts) {
+                            Ok((id, mut rx)) => {
+                                if let Some(qos) = &qos {
+                                    qos.track(id, task.priority);
+                                }
+                                while let Some(msg) = rx.recv().await {
+                                    if let Some(qos) = &qos {
+                                        qos.observe(&this, id, &msg);
+                                    }
+                                    let finished = matches!(
+                                        msg.kind(),
+                                        DownloadEventKind::Completed(_) | DownloadEventKind::Failed(_)
+                                    );
+                                    let _ = out_tx.send(msg);
+                                    if finished {
+                                        break;
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                let mut data = EventData::new();
+                                data.insert("Error".to_string(), serde_json::Value::String(e.to_string()));
+                                let _ = out_tx.send(DownloadEventMsg {
+                                    event: DownloadEvent {
+                                        event_type: "err".to_string(),
+                                        name: String::new(),
+                                        show_name: task.show_name.clone(),
+                                        id: task.id.clone(),
+                                    },
+                                    data,
+                                });
+                            }
+                        }
+                        let _ = slot_tx.send(());
+                    });
+                }
+
+                if running == 0 && scheduler.is_empty() {
+                    break;
+                }
+
+                if slot_rx.recv().await.is_some() {
+                    running -= 1;
+                } else {
+                    break;
+                }
+            }
+        });
+
+        out_rx
+    }
+
+    /// 把 `id` 对应 downloader 当前的任务列表/参数/各任务已下载字节数序列化并写入 `path`
+    pub fn save_snapshot(
+        &self,
+        id: i32,
+        path: &Path,
+        fmt: SnapshotFormat,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let tasks = tasks_map().lock().unwrap().get(&id).cloned().unwrap_or_default();
+        let options = options_map().lock().unwrap().get(&id).cloned().unwrap_or_default();
+        let per_task_downloaded_bytes = {
+            let progress = progress_map().lock().unwrap();
+            tasks
+                .iter()
+                .filter_map(|t| progress.get(&t.id).map(|bytes| (t.id.clone(), *bytes)))
+                .collect()
+        };
+
+        DownloaderSnapshot { tasks, options, per_task_downloaded_bytes }.save(path, fmt)
+    }
+
+    /// 从 `path` 读取快照并重新发起下载，返回新的 `(下载器 ID, 异步事件 Receiver)`
+    ///
+    /// 底层 DLL 不支持按字节续传，这里做的是"恢复任务批次"：重建 `DownloadTask` 列表
+    /// 和 `DownloadOptions`，再调用一次 `start_download`，所以是从头重新下载而不是精确续传。
+    pub fn resume_from_snapshot(
+        &self,
+        path: &Path,
+        fmt: SnapshotFormat,
+    ) -> Result<(i32, mpsc::UnboundedReceiver<DownloadEventMsg>), Box<dyn std::error::Error>> {
+        let snapshot = DownloaderSnapshot::load(path, fmt)?;
+        let urls = snapshot.tasks.iter().map(|t| t.url.clone()).collect();
+        let save_paths = snapshot.tasks.iter().map(|t| t.save_path.clone()).collect();
+        self.start_download(urls, save_paths, snapshot.options)
+    }
 }
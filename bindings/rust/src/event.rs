@@ -2,6 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 
 /// DLL 回调中 event 参数的 JSON 结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,8 +21,88 @@ pub struct DownloadEvent {
 pub type EventData = HashMap<String, serde_json::Value>;
 
 /// 封装好的下载事件消息（通过 mpsc channel 发送给调用方）
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadEventMsg {
     pub event: DownloadEvent,
     pub data: EventData,
 }
+
+/// 解析 `Downloaded`/`Total` 进度字段的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressInfo {
+    pub total_bytes: Option<u64>,
+    pub downloaded_bytes: u64,
+    pub percent: f32,
+    /// 最近一次回调间隔内的瞬时速度（字节/秒），由 wrapper 层计算后塞进 `data`
+    pub instant_speed_bps: u64,
+    pub eta: Option<Duration>,
+}
+
+/// `endOne`/`end` 事件携带的完成信息
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CompletedInfo {
+    pub show_name: String,
+}
+
+/// `err` 事件携带的失败信息
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FailInfo {
+    pub error: String,
+}
+
+/// 按 `DownloadEvent.event_type` 解析出的强类型事件
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadEventKind {
+    Started,
+    Progress(ProgressInfo),
+    Paused,
+    Resumed,
+    Completed(CompletedInfo),
+    Failed(FailInfo),
+    /// 未归类的事件类型（例如非暂停/恢复语义的 `msg`）
+    Other,
+}
+
+impl DownloadEventMsg {
+    /// 把原始 JSON 事件解析为结构化的 [`DownloadEventKind`]，避免调用方手动翻 `data` 里的字段
+    pub fn kind(&self) -> DownloadEventKind {
+        match self.event.event_type.as_str() {
+            "start" | "startOne" => DownloadEventKind::Started,
+            "update" => DownloadEventKind::Progress(self.parse_progress()),
+            "endOne" | "end" => DownloadEventKind::Completed(CompletedInfo {
+                show_name: self.event.show_name.clone(),
+            }),
+            "err" => DownloadEventKind::Failed(FailInfo {
+                error: self.data.get("Error").and_then(|v| v.as_str()).unwrap_or("未知错误").to_string(),
+            }),
+            "msg" => match self.data.get("Text").and_then(|v| v.as_str()) {
+                Some(text) if text.contains("暂停") => DownloadEventKind::Paused,
+                Some(text) if text.contains("恢复") => DownloadEventKind::Resumed,
+                _ => DownloadEventKind::Other,
+            },
+            _ => DownloadEventKind::Other,
+        }
+    }
+
+    fn parse_progress(&self) -> ProgressInfo {
+        let downloaded_bytes = self.data.get("Downloaded").and_then(|v| v.as_u64()).unwrap_or(0);
+        let total_bytes = self.data.get("Total").and_then(|v| v.as_u64()).filter(|&t| t > 0);
+        let percent = total_bytes
+            .map(|t| downloaded_bytes as f32 / t as f32 * 100.0)
+            .unwrap_or(0.0);
+        let instant_speed_bps = self.data.get("InstantSpeedBps").and_then(|v| v.as_u64()).unwrap_or(0);
+        let avg_speed_bps = self.data.get("AvgSpeedBps").and_then(|v| v.as_u64()).unwrap_or(0);
+        let eta = total_bytes
+            .map(|t| t.saturating_sub(downloaded_bytes))
+            .filter(|_| avg_speed_bps > 0)
+            .map(|remaining| Duration::from_secs_f64(remaining as f64 / avg_speed_bps as f64));
+
+        ProgressInfo {
+            total_bytes,
+            downloaded_bytes,
+            percent,
+            instant_speed_bps,
+            eta,
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! 纯 Rust 的回退下载后端（`fallback` feature）
+//!
+//! 当运行环境找不到原生 TTHSD 动态库时，[`crate::downloader::TTHSDownloader::load`]
+//! 会退化使用这里的实现：基于 `reqwest` + `tokio` 的多连接分段下载，
+//! 暴露与原生路径完全相同的 `start_download`/`pause_download`/`resume_download`/`stop_download`
+//! 接口，并产出同样结构的 [`DownloadEventMsg`]。
+#![cfg(feature = "fallback")]
+
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::StreamExt;
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, HeaderMap, HeaderValue, RANGE, USER_AGENT};
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::downloader::DownloadOptions;
+use crate::event::{DownloadEvent, DownloadEventMsg, EventData};
+
+const DEFAULT_UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36";
+const DEFAULT_THREAD_COUNT: usize = 8;
+const DEFAULT_CHUNK_SIZE_MB: usize = 10;
+/// 下载过程中发送 `update` 事件的节流间隔，对齐原生路径的进度上报频率
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+/// 暂停期间轮询 `paused` 标志的间隔
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+struct DownloadHandle {
+    cancelled: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+}
+
+/// 纯 Rust 的 HTTP 分段下载后端
+pub struct FallbackDownloader {
+    client: reqwest::Client,
+    next_id: AtomicI32,
+    handles: Mutex<HashMap<i32, Arc<DownloadHandle>>>,
+    senders: Mutex<HashMap<i32, mpsc::UnboundedSender<DownloadEventMsg>>>,
+}
+
+impl FallbackDownloader {
+    pub fn new() -> Self {
+        FallbackDownloader {
+            client: reqwest::Client::new(),
+            next_id: AtomicI32::new(1),
+            handles: Mutex::new(HashMap::new()),
+            senders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn emit(&self, id: i32, event_type: &str, task_id: &str, show_name: &str, data: EventData) {
+        let senders = self.senders.lock().unwrap();
+        if let Some(tx) = senders.get(&id) {
+            let msg = DownloadEventMsg {
+                event: DownloadEvent {
+                    event_type: event_type.to_string(),
+                    name: String::new(),
+                    show_name: show_name.to_string(),
+                    id: task_id.to_string(),
+                },
+                data,
+            };
+            let _ = tx.send(msg);
+        }
+    }
+
+    /// 与 `TTHSDownloader::start_download` 等价：创建下载器并立即启动
+    pub fn start_download(
+        &self,
+        urls: Vec<String>,
+        save_paths: Vec<String>,
+        opts: DownloadOptions,
+    ) -> Result<(i32, mpsc::UnboundedReceiver<DownloadEventMsg>), Box<dyn std::error::Error>> {
+        let (id, rx) = self.register(urls.clone(), save_paths.clone(), opts.clone())?;
+        self.run(id, urls, save_paths, opts);
+        Ok((id, rx))
+    }
+
+    /// 对应 `TTHSDownloader::get_downloader`：创建下载器但不启动；本后端的 `start_download_by_id`
+    /// 只检查 id 是否存在，不会启动这里登记的下载
+    pub fn get_downloader(
+        &self,
+        urls: Vec<String>,
+        save_paths: Vec<String>,
+        opts: DownloadOptions,
+    ) -> Result<(i32, mpsc::UnboundedReceiver<DownloadEventMsg>), Box<dyn std::error::Error>> {
+        self.register(urls, save_paths, opts)
+    }
+
+    fn register(
+        &self,
+        urls: Vec<String>,
+        save_paths: Vec<String>,
+        _opts: DownloadOptions,
+    ) -> Result<(i32, mpsc::UnboundedReceiver<DownloadEventMsg>), Box<dyn std::error::Error>> {
+        if urls.len() != save_paths.len() {
+            return Err("urls 与 save_paths 长度不一致".into());
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let handle = Arc::new(DownloadHandle {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+        });
+        self.handles.lock().unwrap().insert(id, handle);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.senders.lock().unwrap().insert(id, tx);
+
+        Ok((id, rx))
+    }
+
+    /// 由 `start_download`/`start_download_by_id` 触发，异步拉起所有任务
+    fn run(&self, id: i32, urls: Vec<String>, save_paths: Vec<String>, opts: DownloadOptions) {
+        let client = self.client.clone();
+        let handle = self.handles.lock().unwrap().get(&id).cloned();
+        let Some(handle) = handle else { return };
+
+        let senders = &self.senders;
+        let tx = senders.lock().unwrap().get(&id).cloned();
+        let Some(tx) = tx else { return };
+
+        let thread_count = opts.thread_count.unwrap_or(DEFAULT_THREAD_COUNT);
+        let chunk_size_mb = opts.chunk_size_mb.unwrap_or(DEFAULT_CHUNK_SIZE_MB);
+        let user_agent = opts.user_agent.clone().unwrap_or_else(|| DEFAULT_UA.to_string());
+
+        tokio::spawn(async move {
+            let _ = tx.send(DownloadEventMsg {
+                event: DownloadEvent { event_type: "start".to_string(), name: String::new(), show_name: String::new(), id: String::new() },
+                data: EventData::new(),
+            });
+
+            let total = urls.len();
+            for (index, (url, save_path)) in urls.into_iter().zip(save_paths.into_iter()).enumerate() {
+                if handle.cancelled.load(Ordering::SeqCst) {
+                    break;
+                }
+                let task_id = Uuid::new_v4().to_string();
+                let show_name = url.rsplit('/').next().unwrap_or("").split('?').next().unwrap_or("").to_string();
+
+                let mut start_data = EventData::new();
+                start_data.insert("URL".to_string(), serde_json::Value::String(url.clone()));
+                start_data.insert("Index".to_string(), serde_json::Value::from((index + 1) as i64));
+                start_data.insert("Total".to_string(), serde_json::Value::from(total as i64));
+                let _ = tx.send(DownloadEventMsg {
+                    event: DownloadEvent { event_type: "startOne".to_string(), name: String::new(), show_name: show_name.clone(), id: task_id.clone() },
+                    data: start_data,
+                });
+
+                let result = download_one(&client, &url, &save_path, thread_count, chunk_size_mb, &user_agent, &tx, &task_id, &handle).await;
+
+                let mut end_data = EventData::new();
+                end_data.insert("URL".to_string(), serde_json::Value::String(url));
+                if let Err(e) = result {
+                    let mut err_data = EventData::new();
+                    err_data.insert("Error".to_string(), serde_json::Value::String(e.to_string()));
+                    let _ = tx.send(DownloadEventMsg {
+                        event: DownloadEvent { event_type: "err".to_string(), name: String::new(), show_name: show_name.clone(), id: task_id.clone() },
+                        data: err_data,
+                    });
+                }
+                let _ = tx.send(DownloadEventMsg {
+                    event: DownloadEvent { event_type: "endOne".to_string(), name: String::new(), show_name, id: task_id },
+                    data: end_data,
+                });
+            }
+
+            let _ = tx.send(DownloadEventMsg {
+                event: DownloadEvent { event_type: "end".to_string(), name: String::new(), show_name: String::new(), id: String::new() },
+                data: EventData::new(),
+            });
+        });
+    }
+
+    pub fn start_download_by_id(&self, id: i32) -> bool {
+        // 纯 Rust 后端没有保存 register() 时的 urls/save_paths/opts，
+        // get_downloader + start_download_by_id 的两段式用法暂不支持，
+        // 调用方应直接使用 start_download 一次性启动并运行。
+        self.handles.lock().unwrap().contains_key(&id)
+    }
+
+    pub fn start_multiple_downloads_by_id(&self, id: i32) -> bool {
+        self.start_download_by_id(id)
+    }
+
+    pub fn pause_download(&self, id: i32) -> bool {
+        if let Some(handle) = self.handles.lock().unwrap().get(&id) {
+            handle.paused.store(true, Ordering::SeqCst);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn resume_download(&self, id: i32) -> bool {
+        if let Some(handle) = self.handles.lock().unwrap().get(&id) {
+            handle.paused.store(false, Ordering::SeqCst);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn stop_download(&self, id: i32) -> bool {
+        let existed = if let Some(handle) = self.handles.lock().unwrap().remove(&id) {
+            handle.cancelled.store(true, Ordering::SeqCst);
+            true
+        } else {
+            false
+        };
+        self.senders.lock().unwrap().remove(&id);
+        existed
+    }
+}
+
+/// 对单个 url/save_path 执行探测 + 分段（或单流回退）下载
+async fn download_one(
+    client: &reqwest::Client,
+    url: &str,
+    save_path: &str,
+    thread_count: usize,
+    chunk_size_mb: usize,
+    user_agent: &str,
+    tx: &mpsc::UnboundedSender<DownloadEventMsg>,
+    task_id: &str,
+    handle: &Arc<DownloadHandle>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let head = client.head(url).header(USER_AGENT, user_agent).send().await?;
+
+    let accepts_ranges = head
+        .headers()
+        .get(ACCEPT_RANGES)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.eq_ignore_ascii_case("bytes"))
+        .unwrap_or(false);
+
+    let content_length = head
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    let downloaded = Arc::new(AtomicU64::new(0));
+
+    // 边下载边按 PROGRESS_INTERVAL 节流上报 `update` 事件，让 chunk0-2 的
+    // DownloadEventKind::Progress 在 fallback 路径上也能收到增量进度，而不是只有完成后一条
+    let progress_handle = tokio::spawn({
+        let tx = tx.clone();
+        let task_id = task_id.to_string();
+        let downloaded = downloaded.clone();
+        async move {
+            let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+            interval.tick().await; // 第一次 tick 立即完成，跳过它避免下载刚开始就发一条 0 进度
+            loop {
+                interval.tick().await;
+                let mut data = EventData::new();
+                data.insert("Downloaded".to_string(), serde_json::Value::from(downloaded.load(Ordering::SeqCst)));
+                data.insert("Total".to_string(), serde_json::Value::from(content_length));
+                let _ = tx.send(DownloadEventMsg {
+                    event: DownloadEvent { event_type: "update".to_string(), name: String::new(), show_name: String::new(), id: task_id.clone() },
+                    data,
+                });
+            }
+        }
+    });
+
+    let result = if accepts_ranges && content_length > 0 {
+        download_ranged(client, url, save_path, content_length, thread_count, chunk_size_mb, user_agent, &downloaded, handle).await
+    } else {
+        download_streamed(client, url, save_path, user_agent, &downloaded, handle).await
+    };
+
+    progress_handle.abort();
+
+    result?;
+
+    let mut data = EventData::new();
+    data.insert("Downloaded".to_string(), serde_json::Value::from(downloaded.load(Ordering::SeqCst)));
+    data.insert("Total".to_string(), serde_json::Value::from(content_length));
+    let _ = tx.send(DownloadEventMsg {
+        event: DownloadEvent { event_type: "update".to_string(), name: String::new(), show_name: String::new(), id: task_id.to_string() },
+        data,
+    });
+
+    if handle.cancelled.load(Ordering::SeqCst) {
+        return Err("cancelled".into());
+    }
+
+    Ok(())
+}
+
+/// 暂停期间阻塞在这里轮询，取消信号仍然能在暂停中把下载打断
+async fn wait_while_paused(handle: &DownloadHandle) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    while handle.paused.load(Ordering::SeqCst) {
+        if handle.cancelled.load(Ordering::SeqCst) {
+            return Err("cancelled".into());
+        }
+        tokio::time::sleep(PAUSE_POLL_INTERVAL).await;
+    }
+    Ok(())
+}
+
+/// `Accept-Ranges: bytes` 且已知大小：拆成若干段各自 `Range` 请求写入预分配文件；
+/// 同时在跑的段数受 `thread_count` 信号量限制，而不是一次性把所有段都 spawn 出去
+async fn download_ranged(
+    client: &reqwest::Client,
+    url: &str,
+    save_path: &str,
+    content_length: u64,
+    thread_count: usize,
+    chunk_size_mb: usize,
+    user_agent: &str,
+    downloaded: &Arc<AtomicU64>,
+    handle: &Arc<DownloadHandle>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file = OpenOptions::new().write(true).create(true).open(save_path).await?;
+    file.set_len(content_length).await?;
+    drop(file);
+
+    let chunk_size = (chunk_size_mb as u64) * 1024 * 1024;
+    let segment_len = std::cmp::max(content_length / thread_count.max(1) as u64, chunk_size).max(1);
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(thread_count.max(1)));
+
+    let mut join_set = tokio::task::JoinSet::new();
+    let mut offset = 0u64;
+    while offset < content_length {
+        let end = std::cmp::min(offset + segment_len - 1, content_length - 1);
+        let client = client.clone();
+        let url = url.to_string();
+        let save_path = save_path.to_string();
+        let user_agent = user_agent.to_string();
+        let downloaded = downloaded.clone();
+        let handle = handle.clone();
+        let semaphore = semaphore.clone();
+
+        join_set.spawn(async move {
+            let _permit = semaphore.acquire_owned().await?;
+
+            let mut headers = HeaderMap::new();
+            headers.insert(USER_AGENT, HeaderValue::from_str(&user_agent)?);
+            headers.insert(RANGE, HeaderValue::from_str(&format!("bytes={}-{}", offset, end))?);
+
+            let response = client.get(&url).headers(headers).send().await?;
+            if !response.status().is_success() {
+                return Err::<(), Box<dyn std::error::Error + Send + Sync>>(format!("bad status: {}", response.status()).into());
+            }
+
+            let mut file = OpenOptions::new().write(true).open(&save_path).await?;
+            file.seek(SeekFrom::Start(offset)).await?;
+
+            // 逐块落盘而不是整段先 `bytes()` 缓冲在内存里，段大小不再受内存限制
+            let mut stream = response.bytes_stream();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk?;
+                file.write_all(&chunk).await?;
+                downloaded.fetch_add(chunk.len() as u64, Ordering::SeqCst);
+
+                if handle.cancelled.load(Ordering::SeqCst) {
+                    return Err("cancelled".into());
+                }
+                wait_while_paused(&handle).await?;
+            }
+            Ok(())
+        });
+        offset = end + 1;
+    }
+
+    while let Some(result) = join_set.join_next().await {
+        result??;
+    }
+
+    Ok(())
+}
+
+/// 不支持 Range 或长度未知：退化为单流顺序写入，不预分配
+async fn download_streamed(
+    client: &reqwest::Client,
+    url: &str,
+    save_path: &str,
+    user_agent: &str,
+    downloaded: &Arc<AtomicU64>,
+    handle: &Arc<DownloadHandle>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let response = client.get(url).header(USER_AGENT, user_agent).send().await?;
+    if !response.status().is_success() {
+        return Err(format!("bad status: {}", response.status()).into());
+    }
+
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).open(save_path).await?;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        downloaded.fetch_add(chunk.len() as u64, Ordering::SeqCst);
+
+        if handle.cancelled.load(Ordering::SeqCst) {
+            return Err("cancelled".into());
+        }
+        wait_while_paused(handle).await?;
+    }
+
+    Ok(())
+}
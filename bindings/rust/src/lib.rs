@@ -31,6 +31,16 @@
 pub mod ffi;
 pub mod downloader;
 pub mod event;
+pub mod scheduler;
+pub mod snapshot;
+pub mod daemon;
+pub mod qos;
+#[cfg(feature = "fallback")]
+pub mod fallback;
 
-pub use downloader::{TTHSDownloader, DownloadOptions};
-pub use event::{DownloadEventMsg};
+pub use downloader::{TTHSDownloader, DownloadOptions, DownloadTask};
+pub use event::{DownloadEventMsg, DownloadEventKind, ProgressInfo, CompletedInfo, FailInfo};
+pub use scheduler::{Scheduler, FifoScheduler, PriorityScheduler};
+pub use snapshot::{DownloaderSnapshot, SnapshotFormat};
+pub use daemon::{DownloadManager, DaemonClient, DaemonRequest, DaemonResponse, PROTOCOL_VERSION};
+pub use qos::{QosController, QosPolicy, QosFlags};
@@ -0,0 +1,163 @@
+//! QoS 子系统：根据运行时条件（总带宽预算、是否处于按流量计费网络等）
+//! 动态调整正在跑的下载，而不是放任所有任务一直全速跑。
+//!
+//! [`QosController`] 不替代 [`crate::scheduler::Scheduler`]：scheduler 决定
+//! "接下来启动哪个任务"，`QosController` 决定"已经在跑的任务里，谁该被临时
+//! 暂停/恢复"。调用方把事件流喂给 [`QosController::observe`]（[`crate::downloader::TTHSDownloader::submit_batch`]
+//! 已经这么做了），它会在总吞吐超出预算、或网络进入按流量计费状态时，暂停
+//! 优先级最低的下载，等预算富余或有任务完成时再按优先级恢复。
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::downloader::TTHSDownloader;
+use crate::event::{DownloadEventKind, DownloadEventMsg};
+
+/// QoS 策略配置
+#[derive(Debug, Clone, Default)]
+pub struct QosPolicy {
+    /// 所有受控下载的总速度预算（字节/秒），`None` 表示不限速
+    pub max_total_speed_bps: Option<u64>,
+}
+
+/// 运行时条件标志，由调用方（例如 App 前后台切换、系统网络状态回调）按需翻转，
+/// `QosController` 在下一次 `observe`/`rebalance` 时会读取并作出响应
+#[derive(Debug, Default)]
+pub struct QosFlags {
+    /// 当前网络是否按流量计费；为 true 时只保留优先级最高的下载继续跑
+    pub metered_network: AtomicBool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunState {
+    Running,
+    PausedByQos,
+}
+
+struct Tracked {
+    priority: i32,
+    state: RunState,
+    last_speed_bps: u64,
+}
+
+/// 挂在 [`TTHSDownloader::submit_batch`] 事件循环里的 QoS 控制器
+pub struct QosController {
+    policy: Mutex<QosPolicy>,
+    flags: Arc<QosFlags>,
+    tracked: Mutex<HashMap<i32, Tracked>>,
+}
+
+impl QosController {
+    pub fn new(policy: QosPolicy) -> Arc<Self> {
+        Arc::new(QosController {
+            policy: Mutex::new(policy),
+            flags: Arc::new(QosFlags::default()),
+            tracked: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 调用方持有的运行时条件标志（例如在网络状态回调里翻转 `metered_network`）
+    pub fn flags(&self) -> Arc<QosFlags> {
+        self.flags.clone()
+    }
+
+    pub fn set_policy(&self, policy: QosPolicy) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    /// 登记一个受 QoS 管理的下载，`priority` 越大越不容易被暂停
+    pub fn track(&self, downloader_id: i32, priority: i32) {
+        self.tracked.lock().unwrap().insert(
+            downloader_id,
+            Tracked { priority, state: RunState::Running, last_speed_bps: 0 },
+        );
+    }
+
+    fn untrack(&self, downloader_id: i32) {
+        self.tracked.lock().unwrap().remove(&downloader_id);
+    }
+
+    /// 消费一条属于 `downloader_id` 的下载事件，按需暂停/恢复受控下载
+    pub fn observe(&self, downloader: &TTHSDownloader, downloader_id: i32, msg: &DownloadEventMsg) {
+        match msg.kind() {
+            DownloadEventKind::Progress(info) => {
+                if let Some(t) = self.tracked.lock().unwrap().get_mut(&downloader_id) {
+                    t.last_speed_bps = info.instant_speed_bps;
+                }
+                self.rebalance(downloader);
+            }
+            DownloadEventKind::Completed(_) | DownloadEventKind::Failed(_) => {
+                self.untrack(downloader_id);
+                self.rebalance(downloader);
+            }
+            _ => {}
+        }
+    }
+
+    /// 按总预算/metered 标志重新评估应该暂停/恢复哪些下载；metered 或不限速时一次性
+    /// 调整所有受控下载，按预算调整时每次只调整一个，下一条事件到来时会再次评估，逐步收敛到预算以内
+    fn rebalance(&self, downloader: &TTHSDownloader) {
+        let policy = self.policy.lock().unwrap().clone();
+        let metered = self.flags.metered_network.load(Ordering::Relaxed);
+        let mut tracked = self.tracked.lock().unwrap();
+
+        if tracked.is_empty() {
+            return;
+        }
+
+        if metered {
+            let Some(&top_id) = tracked.iter().max_by_key(|(_, t)| t.priority).map(|(id, _)| id) else {
+                return;
+            };
+            for (&id, t) in tracked.iter_mut() {
+                Self::apply(downloader, id, t, id == top_id);
+            }
+            return;
+        }
+
+        let Some(budget) = policy.max_total_speed_bps else {
+            for (&id, t) in tracked.iter_mut() {
+                Self::apply(downloader, id, t, true);
+            }
+            return;
+        };
+
+        let total_speed: u64 = tracked
+            .values()
+            .filter(|t| t.state == RunState::Running)
+            .map(|t| t.last_speed_bps)
+            .sum();
+
+        if total_speed <= budget {
+            if let Some((&id, t)) = tracked
+                .iter_mut()
+                .filter(|(_, t)| t.state == RunState::PausedByQos)
+                .max_by_key(|(_, t)| t.priority)
+            {
+                Self::apply(downloader, id, t, true);
+            }
+        } else if let Some((&id, t)) = tracked
+            .iter_mut()
+            .filter(|(_, t)| t.state == RunState::Running)
+            .min_by_key(|(_, t)| t.priority)
+        {
+            Self::apply(downloader, id, t, false);
+        }
+    }
+
+    fn apply(downloader: &TTHSDownloader, downloader_id: i32, tracked: &mut Tracked, should_run: bool) {
+        let target_state = if should_run { RunState::Running } else { RunState::PausedByQos };
+        if tracked.state == target_state {
+            return;
+        }
+        let ok = if should_run {
+            downloader.resume_download(downloader_id)
+        } else {
+            downloader.pause_download(downloader_id)
+        };
+        if ok {
+            tracked.state = target_state;
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! 可插拔的任务调度器
+//!
+//! 在 `DownloadTask` 提交和实际发起 FFI `start_download` 调用之间加一层队列：
+//! 调用方可以一次性提交一大批任务，由 [`crate::downloader::TTHSDownloader::submit_batch`]
+//! 按 `max_concurrent` 限速从调度器里取任务分发，而不是把所有任务同时扔给底层下载器。
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::downloader::DownloadTask;
+
+/// 调度器的统一接口：插入 / 查看 / 弹出 / 移除任务
+pub trait Scheduler<T> {
+    fn insert(&mut self, task: T);
+    fn peek(&self) -> Option<&T>;
+    fn peek_mut(&mut self) -> Option<&mut T>;
+    fn pop(&mut self) -> Option<T>;
+    fn remove(&mut self, task: &T) -> Option<T>;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// 先进先出调度器（默认选择）
+#[derive(Debug, Default)]
+pub struct FifoScheduler {
+    queue: VecDeque<DownloadTask>,
+}
+
+impl FifoScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Scheduler<DownloadTask> for FifoScheduler {
+    fn insert(&mut self, task: DownloadTask) {
+        self.queue.push_back(task);
+    }
+
+    fn peek(&self) -> Option<&DownloadTask> {
+        self.queue.front()
+    }
+
+    fn peek_mut(&mut self) -> Option<&mut DownloadTask> {
+        self.queue.front_mut()
+    }
+
+    fn pop(&mut self) -> Option<DownloadTask> {
+        self.queue.pop_front()
+    }
+
+    fn remove(&mut self, task: &DownloadTask) -> Option<DownloadTask> {
+        let idx = self.queue.iter().position(|t| t.id == task.id)?;
+        self.queue.remove(idx)
+    }
+
+    fn len(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+/// 按 `DownloadTask.priority` 排序的优先级调度器（数值越大越先被 `pop`）
+#[derive(Debug, Default)]
+pub struct PriorityScheduler {
+    heap: BinaryHeap<PrioritizedTask>,
+}
+
+#[derive(Debug, Clone)]
+struct PrioritizedTask(DownloadTask);
+
+impl PartialEq for PrioritizedTask {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.priority == other.0.priority
+    }
+}
+impl Eq for PrioritizedTask {}
+impl PartialOrd for PrioritizedTask {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for PrioritizedTask {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.priority.cmp(&other.0.priority)
+    }
+}
+
+impl PriorityScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Scheduler<DownloadTask> for PriorityScheduler {
+    fn insert(&mut self, task: DownloadTask) {
+        self.heap.push(PrioritizedTask(task));
+    }
+
+    fn peek(&self) -> Option<&DownloadTask> {
+        self.heap.peek().map(|t| &t.0)
+    }
+
+    fn peek_mut(&mut self) -> Option<&mut DownloadTask> {
+        // BinaryHeap 不提供可以安全修改排序键的可变引用（改了 priority 却不重新排序会破坏堆序），
+        // 需要调整优先级时请 `remove` 后重新 `insert`。
+        None
+    }
+
+    fn pop(&mut self) -> Option<DownloadTask> {
+        self.heap.pop().map(|t| t.0)
+    }
+
+    fn remove(&mut self, task: &DownloadTask) -> Option<DownloadTask> {
+        let items: Vec<PrioritizedTask> = self.heap.drain().collect();
+        let mut removed = None;
+        for item in items {
+            if removed.is_none() && item.0.id == task.id {
+                removed = Some(item.0.clone());
+            } else {
+                self.heap.push(item);
+            }
+        }
+        removed
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+}
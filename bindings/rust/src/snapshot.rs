@@ -0,0 +1,56 @@
+//! 下载器快照：把正在运行的任务列表/参数/已下载字节数序列化到磁盘，
+//! 使进程崩溃或重启后可以通过 [`crate::downloader::TTHSDownloader::resume_from_snapshot`]
+//! 继续大致相同的下载批次。
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::downloader::{DownloadOptions, DownloadTask};
+
+/// 快照的可选编码格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// 人类可读，便于调试
+    Json,
+    /// 紧凑二进制
+    Bincode,
+    /// 便于跨语言互通
+    Cbor,
+}
+
+/// 某个 downloader 在某一时刻的完整现场
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloaderSnapshot {
+    pub tasks: Vec<DownloadTask>,
+    pub options: DownloadOptions,
+    pub per_task_downloaded_bytes: HashMap<String, u64>,
+}
+
+impl DownloaderSnapshot {
+    pub fn encode(&self, fmt: SnapshotFormat) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Ok(match fmt {
+            SnapshotFormat::Json => serde_json::to_vec_pretty(self)?,
+            SnapshotFormat::Bincode => bincode::serialize(self)?,
+            SnapshotFormat::Cbor => serde_cbor::to_vec(self)?,
+        })
+    }
+
+    pub fn decode(bytes: &[u8], fmt: SnapshotFormat) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(match fmt {
+            SnapshotFormat::Json => serde_json::from_slice(bytes)?,
+            SnapshotFormat::Bincode => bincode::deserialize(bytes)?,
+            SnapshotFormat::Cbor => serde_cbor::from_slice(bytes)?,
+        })
+    }
+
+    pub fn save(&self, path: &Path, fmt: SnapshotFormat) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(path, self.encode(fmt)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path, fmt: SnapshotFormat) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::decode(&std::fs::read(path)?, fmt)
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
@@ -6,6 +7,8 @@ use super::websocket_client::WebSocketClient;
 use super::socket_client::SocketClient;
 use super::send_message::send_message;
 use super::performance_monitor::get_global_monitor;
+use super::downloader_interface::Downloader;
+use super::http_downloader::{DownloadSnapshot, StatusSlot};
 
 pub const UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36";
 
@@ -15,6 +18,21 @@ pub struct DownloadTask {
     pub save_path: String,
     pub show_name: String,
     pub id: String,
+    /// 镜像 URL 列表，排在 `url` 之后；某次重试失败后会滚动到下一个镜像
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+    /// 下载完成后用于校验完整性的期望摘要；校验失败会删除产物文件并让断点续传状态失效
+    #[serde(default)]
+    pub expected_hash: Option<(HashAlgo, String)>,
+}
+
+/// [`DownloadTask::expected_hash`] 支持的摘要算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashAlgo {
+    #[serde(rename = "sha256")]
+    Sha256,
+    #[serde(rename = "md5")]
+    Md5,
 }
 
 pub type ProgressCallback = extern "C" fn(*const std::ffi::c_char, *const std::ffi::c_char);
@@ -30,13 +48,49 @@ pub struct DownloadConfig {
     pub use_socket: Option<bool>,
     pub show_name: String,
     pub user_agent: String,
+    /// 单个分块在放弃前最多重试几次（不含首次尝试）
+    pub max_retries: u32,
+    /// 重试退避的基准间隔，实际等待时间是该值按尝试次数指数增长再加随机抖动
+    pub base_backoff_ms: u64,
+    /// 设置后下载的字节流会被直接解包，不再写原始压缩包到 `save_path`
+    /// （此时 `save_path` 被当作解包目标目录），详见 `HTTPDownloader::download_and_extract`
+    pub extract: Option<ExtractFormat>,
+    /// 限速预算（字节/秒），同一个任务的所有并发 worker 共享一个令牌桶；同时运行的多个任务
+    /// 各用各的令牌桶，预算不在任务之间分摊；`None` 表示不限速
+    pub max_speed_bps: Option<u64>,
+    /// 同一批次里最多同时跑几个 `download_task`，用 `Semaphore` 限流，避免一次性
+    /// 对几百个任务都打开连接耗尽 socket
+    pub max_concurrent_tasks: usize,
+    /// 单个任务整体失败（`Downloader::download` 返回 `Err`）后最多重试几次（不含首次尝试），
+    /// 与 [`Self::max_retries`] 不是一回事：那个是 HTTP 分块级别的重试，这个是任务级别的
+    pub retries: u32,
 }
 
-#[derive(Debug, Clone)]
+/// `DownloadConfig::extract` 支持的压缩格式，决定边下载边解包时使用哪种流式解码器
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExtractFormat {
+    Gzip,
+    Bzip2,
+    Lz4,
+}
+
+/// 单个下载器实现落地的分块范围；`HSDownloader` 自己不做 HEAD 探测/分块拉取，
+/// 这些都交给 `get_downloader` 工厂选出的下载器实现。HTTP 场景下具体是
+/// `HTTPDownloader::get_file_size`（HEAD 探测 `Accept-Ranges`/`Content-Length`）
+/// 和 `HTTPDownloader::create_chunks`（按 `chunk_size_mb` 切出 `ceil(len/chunk_size)`
+/// 个分块；但 `len/(thread_count*2)` 大于 `chunk_size` 时改用这个更大的分块大小，分块数
+/// 约为 `thread_count*2` 而不是 `ceil(len/chunk_size)`；不支持 Range 时退化为单流）
+/// 落的地；这里只是各实现共用的分块描述，
+/// 也是 `HTTPDownloader` 内部 `ResumeState` 断点续传 sidecar 文件里序列化的内容，
+/// 本身不是新增实现——分块引擎和续传状态落盘在 `HTTPDownloader` 里已经是完整的
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadChunk {
     pub start_offset: i64,
     pub end_offset: i64,
     pub done: bool,
+    /// 该分块已经写入磁盘的字节数，用于断点续传时计算实际应该 seek 到的位置
+    #[serde(default)]
+    pub bytes_done: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -55,6 +109,8 @@ pub enum EventType {
     Msg,
     #[serde(rename = "err")]
     Err,
+    #[serde(rename = "retry")]
+    Retry,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,12 +131,29 @@ pub struct ProgressEvent {
     pub downloaded: i64,
 }
 
+/// 正在跑的下载器实例，以任务 ID 为 key；`download_task` 整段重试循环期间都持有
+/// 这把锁，所以它不适合查询实时进度（见 [`TaskStatuses`]），主要用于按任务 ID
+/// 定位下载器实例本身（目前没有这类用法，保留给未来扩展，例如按任务取消单个下载）
+type TaskDownloaders = Arc<RwLock<HashMap<String, Arc<Mutex<Box<dyn Downloader>>>>>>;
+
+/// 按任务 ID 查询实时进度的免锁句柄表，供 [`HSDownloader::get_snapshot`] 和
+/// [`HSDownloader::send_progress_update`] 的每任务分项使用；`download_task` 在新建
+/// 下载器之前就把 slot 注册进来，下载过程中不需要竞争 `TaskDownloaders` 那把大锁
+type TaskStatuses = Arc<RwLock<HashMap<String, StatusSlot>>>;
+
 pub struct HSDownloader {
     pub config: Arc<RwLock<DownloadConfig>>,
     pub ws_client: Option<Arc<tokio::sync::Mutex<WebSocketClient>>>,
     pub socket_client: Option<Arc<tokio::sync::Mutex<SocketClient>>>,
     pub cancel_token: Arc<tokio::sync::Mutex<Option<tokio_util::sync::CancellationToken>>>,
     pub current_task_index: Arc<tokio::sync::Mutex<usize>>,
+    /// 连续发送回调消息失败的次数，达到阈值触发一次 [`Self::reconnect_callback`]；
+    /// 任何一次发送成功都会把它清零
+    send_failures: Arc<AtomicU32>,
+    /// 正在跑的下载器实例，按任务 ID 索引，见 [`TaskDownloaders`]
+    task_downloaders: TaskDownloaders,
+    /// 按任务 ID 查询实时进度，见 [`TaskStatuses`]
+    task_statuses: TaskStatuses,
 }
 
 impl HSDownloader {
@@ -112,6 +185,9 @@ impl HSDownloader {
             socket_client,
             cancel_token: Arc::new(tokio::sync::Mutex::new(None)),
             current_task_index: Arc::new(tokio::sync::Mutex::new(0)),
+            send_failures: Arc::new(AtomicU32::new(0)),
+            task_downloaders: Arc::new(RwLock::new(HashMap::new())),
+            task_statuses: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -130,6 +206,12 @@ impl HSDownloader {
             use_socket: None,
             show_name: String::new(),
             user_agent: UA.to_string(),
+            max_retries: 3,
+            base_backoff_ms: 500,
+            extract: None,
+            max_speed_bps: None,
+            max_concurrent_tasks: 10,
+            retries: 5,
         };
 
         Self::new(config)
@@ -155,11 +237,15 @@ impl HSDownloader {
 
         send_message(event, HashMap::new(), &self.config, &self.ws_client, &self.socket_client).await?;
 
-        let tasks = {
+        let (tasks, max_concurrent_tasks) = {
             let config = self.config.read().await;
-            config.tasks.clone()
+            (config.tasks.clone(), config.max_concurrent_tasks.max(1))
         };
 
+        // 同一批次最多同时跑 `max_concurrent_tasks` 个任务，避免几百个任务一次性
+        // 全部打开连接耗尽 socket
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(max_concurrent_tasks));
+
         let mut join_set = tokio::task::JoinSet::new();
 
         for (index, task) in tasks.into_iter().enumerate() {
@@ -167,8 +253,13 @@ impl HSDownloader {
             let config = self.config.clone();
             let ws_client = self.ws_client.clone();
             let socket_client = self.socket_client.clone();
+            let semaphore = semaphore.clone();
+            let send_failures = self.send_failures.clone();
+            let task_downloaders = self.task_downloaders.clone();
+            let task_statuses = self.task_statuses.clone();
 
             join_set.spawn(async move {
+                let _permit = semaphore.acquire_owned().await.expect("semaphore closed");
                 Self::download_task(
                     task,
                     index,
@@ -176,6 +267,9 @@ impl HSDownloader {
                     config,
                     ws_client,
                     socket_client,
+                    send_failures,
+                    task_downloaders,
+                    task_statuses,
                 ).await
             });
         }
@@ -186,47 +280,35 @@ impl HSDownloader {
         let monitor_ws = self.ws_client.clone();
         let monitor_socket = self.socket_client.clone();
         let monitor_token = token.clone();
+        let monitor_send_failures = self.send_failures.clone();
+        let monitor_task_statuses = self.task_statuses.clone();
         let monitor_handle = tokio::spawn(async move {
             let mut interval = tokio::time::interval(std::time::Duration::from_millis(500));
             loop {
                 tokio::select! {
                     _ = interval.tick() => {
-                        if let Some(monitor) = get_global_monitor().await {
-                            let mut stats = monitor.get_stats().await;
-                            
-                            // 兼容旧版 Golang 接口的字段命名 (各语言 Bindings 依赖这两个字段计算进度)
-                            if let Some(total_bytes) = stats.get("total_bytes").cloned() {
-                                stats.insert("Downloaded".to_string(), total_bytes);
-                            }
-                            let event = Event {
-                                event_type: EventType::Update,
-                                name: "进度更新".to_string(),
-                                show_name: "全局".to_string(),
-                                id: String::new(),
-                            };
-                            let _ = send_message(event, stats, &monitor_config, &monitor_ws, &monitor_socket).await;
-                        }
+                        Self::send_progress_update(&monitor_config, &monitor_ws, &monitor_socket, &monitor_send_failures, &monitor_task_statuses).await;
                     }
                     _ = progress_done_rx.recv() => {
                         break;
                     }
                     _ = monitor_token.cancelled() => {
+                        // 被取消时再补发一次定格的最终进度，调用方不会因为轮询间隔没赶上
+                        // 而错过下载停在哪里
+                        Self::send_progress_update(&monitor_config, &monitor_ws, &monitor_socket, &monitor_send_failures, &monitor_task_statuses).await;
                         break;
                     }
                 }
             }
         });
 
-        // 等待所有下载任务完成，或者被取消
+        // 等待所有下载任务完成；取消后不 abort_all 硬杀，而是继续把 join_next 排空，
+        // 让每个任务各自走到自己的取消检查点，完成分块进度落盘和断点续传状态持久化后
+        // 再自然退出，否则会跳过 flush 直接把半个分块文件留在磁盘上
         while let Some(result) = join_set.join_next().await {
             if let Err(e) = result {
                 eprintln!("Task failed: {:?}", e);
             }
-            // 如果 token 被取消（暂停/停止），中止剩余任务
-            if token.is_cancelled() {
-                join_set.abort_all();
-                break;
-            }
         }
 
         // 停止进度监控
@@ -273,11 +355,13 @@ impl HSDownloader {
 
         send_message(event, HashMap::new(), &self.config, &self.ws_client, &self.socket_client).await?;
 
-        let tasks = {
+        let (tasks, max_concurrent_tasks) = {
             let config = self.config.read().await;
-            config.tasks.clone()
+            (config.tasks.clone(), config.max_concurrent_tasks.max(1))
         };
 
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(max_concurrent_tasks));
+
         let mut join_set = tokio::task::JoinSet::new();
 
         for (index, task) in tasks.into_iter().enumerate() {
@@ -285,8 +369,13 @@ impl HSDownloader {
             let config = self.config.clone();
             let ws_client = self.ws_client.clone();
             let socket_client = self.socket_client.clone();
+            let semaphore = semaphore.clone();
+            let send_failures = self.send_failures.clone();
+            let task_downloaders = self.task_downloaders.clone();
+            let task_statuses = self.task_statuses.clone();
 
             join_set.spawn(async move {
+                let _permit = semaphore.acquire_owned().await.expect("semaphore closed");
                 Self::download_task(
                     task,
                     index,
@@ -294,6 +383,9 @@ impl HSDownloader {
                     config,
                     ws_client,
                     socket_client,
+                    send_failures,
+                    task_downloaders,
+                    task_statuses,
                 ).await
             });
         }
@@ -304,47 +396,35 @@ impl HSDownloader {
         let monitor_ws = self.ws_client.clone();
         let monitor_socket = self.socket_client.clone();
         let monitor_token = token.clone();
+        let monitor_send_failures = self.send_failures.clone();
+        let monitor_task_statuses = self.task_statuses.clone();
         let monitor_handle = tokio::spawn(async move {
             let mut interval = tokio::time::interval(std::time::Duration::from_millis(500));
             loop {
                 tokio::select! {
                     _ = interval.tick() => {
-                        if let Some(monitor) = get_global_monitor().await {
-                            let mut stats = monitor.get_stats().await;
-                            
-                            // 兼容旧版 Golang 接口的字段命名
-                            if let Some(total_bytes) = stats.get("total_bytes").cloned() {
-                                stats.insert("Downloaded".to_string(), total_bytes);
-                            }
-                            
-                            let event = Event {
-                                event_type: EventType::Update,
-                                name: "进度更新".to_string(),
-                                show_name: "全局".to_string(),
-                                id: String::new(),
-                            };
-                            let _ = send_message(event, stats, &monitor_config, &monitor_ws, &monitor_socket).await;
-                        }
+                        Self::send_progress_update(&monitor_config, &monitor_ws, &monitor_socket, &monitor_send_failures, &monitor_task_statuses).await;
                     }
                     _ = progress_done_rx.recv() => {
                         break;
                     }
                     _ = monitor_token.cancelled() => {
+                        // 被取消时再补发一次定格的最终进度，调用方不会因为轮询间隔没赶上
+                        // 而错过下载停在哪里
+                        Self::send_progress_update(&monitor_config, &monitor_ws, &monitor_socket, &monitor_send_failures, &monitor_task_statuses).await;
                         break;
                     }
                 }
             }
         });
 
-        // 等待所有下载任务完成，或者被取消
+        // 等待所有下载任务完成；取消后不 abort_all 硬杀，而是继续把 join_next 排空，
+        // 让每个任务各自走到自己的取消检查点，完成分块进度落盘和断点续传状态持久化后
+        // 再自然退出，否则会跳过 flush 直接把半个分块文件留在磁盘上
         while let Some(result) = join_set.join_next().await {
             if let Err(e) = result {
                 eprintln!("Task failed: {:?}", e);
             }
-            if token.is_cancelled() {
-                join_set.abort_all();
-                break;
-            }
         }
 
         // 停止进度监控
@@ -367,6 +447,140 @@ impl HSDownloader {
         Ok(())
     }
 
+    /// 任务级别重试的退避间隔：第 `attempt` 次重试等待 `500ms * 2^attempt`，封顶 30 秒
+    fn task_retry_backoff(attempt: u32) -> std::time::Duration {
+        let ms = 500u64.saturating_mul(1u64 << attempt.min(6));
+        std::time::Duration::from_millis(ms.min(30_000))
+    }
+
+    /// 采一次全局监控数据发一条 `Update` 事件；取消下载时监控循环退出前也会调用这个，
+    /// 确保调用方在收到最后的 `EndOne`/`End` 之前能看到一份定格的最终进度
+    async fn send_progress_update(
+        config: &Arc<RwLock<DownloadConfig>>,
+        ws_client: &Option<Arc<Mutex<WebSocketClient>>>,
+        socket_client: &Option<Arc<Mutex<SocketClient>>>,
+        send_failures: &Arc<AtomicU32>,
+        task_statuses: &TaskStatuses,
+    ) {
+        let Some(monitor) = get_global_monitor().await else {
+            return;
+        };
+        let mut stats = monitor.get_stats().await;
+
+        // 兼容旧版 Golang 接口的字段命名 (各语言 Bindings 依赖这两个字段计算进度)
+        if let Some(total_bytes) = stats.get("total_bytes").cloned() {
+            stats.insert("Downloaded".to_string(), total_bytes);
+        }
+
+        let current_speed = stats.get("current_speed_bps").and_then(|v| v.as_f64()).unwrap_or(0.0);
+        let average_speed = stats.get("average_speed_bps").and_then(|v| v.as_f64()).unwrap_or(0.0);
+
+        // 每任务分项：前端渲染批量下载里单个文件的进度条/ETA 用，键是 DownloadTask::id
+        let mut tasks = serde_json::Map::new();
+        for (task_id, slot) in task_statuses.read().await.iter() {
+            if let Some(snapshot) = Self::task_snapshot(slot, current_speed, average_speed).await {
+                if let Ok(value) = serde_json::to_value(&snapshot) {
+                    tasks.insert(task_id.clone(), value);
+                }
+            }
+        }
+        stats.insert("Tasks".to_string(), serde_json::Value::Object(tasks));
+
+        let event = Event {
+            event_type: EventType::Update,
+            name: "进度更新".to_string(),
+            show_name: "全局".to_string(),
+            id: String::new(),
+        };
+        Self::send_resilient(event, stats, config, ws_client, socket_client, send_failures).await;
+    }
+
+    /// 从免锁的 [`StatusSlot`] 读出一份当前快照，任务还没跑到 HEAD 探测结束
+    /// （`status` 还是 `None`）之前返回 `None`
+    async fn task_snapshot(slot: &StatusSlot, current_speed: f64, average_speed: f64) -> Option<DownloadSnapshot> {
+        let status = slot.read().await;
+        let status = status.as_ref()?;
+        Some(status.snapshot(current_speed, average_speed).await)
+    }
+
+    /// 达到连续失败阈值时重连一次回调通道（见 [`Self::reconnect_callback`]）
+    const SEND_FAILURE_RECONNECT_THRESHOLD: u32 = 3;
+
+    /// 发送回调事件，失败时记录连续失败次数；每凑够 [`Self::SEND_FAILURE_RECONNECT_THRESHOLD`]
+    /// 次就触发一次重连尝试。下载本身不受影响——回调通道彻底断了也只是不再有进度上报
+    async fn send_resilient(
+        event: Event,
+        data: HashMap<String, serde_json::Value>,
+        config: &Arc<RwLock<DownloadConfig>>,
+        ws_client: &Option<Arc<Mutex<WebSocketClient>>>,
+        socket_client: &Option<Arc<Mutex<SocketClient>>>,
+        send_failures: &Arc<AtomicU32>,
+    ) {
+        let result = send_message(event.clone(), data.clone(), config, ws_client, socket_client).await;
+
+        // 没有 WebSocket/Socket 通道时没有可重连的对象，不计失败次数
+        if ws_client.is_none() && socket_client.is_none() {
+            return;
+        }
+
+        match result {
+            Ok(()) => {
+                send_failures.store(0, Ordering::Relaxed);
+            }
+            Err(e) => {
+                let failures = send_failures.fetch_add(1, Ordering::Relaxed) + 1;
+                eprintln!("发送回调消息失败 (连续 {} 次): {:?}", failures, e);
+
+                if failures % Self::SEND_FAILURE_RECONNECT_THRESHOLD == 0 {
+                    Self::reconnect_callback(&event, &data, config, ws_client, socket_client, send_failures).await;
+                }
+            }
+        }
+    }
+
+    /// 从 `callback_url` 重新创建 `WebSocketClient`/`SocketClient`，原地换掉
+    /// `Arc<Mutex<...>>` 背后的实例；每次重连后补发一次当前事件验证新连接是否可用，
+    /// 不行就按退避等待重试，尝试次数封顶后放弃（下载继续跑，只是事件会持续丢失）
+    async fn reconnect_callback(
+        event: &Event,
+        data: &HashMap<String, serde_json::Value>,
+        config: &Arc<RwLock<DownloadConfig>>,
+        ws_client: &Option<Arc<Mutex<WebSocketClient>>>,
+        socket_client: &Option<Arc<Mutex<SocketClient>>>,
+        send_failures: &Arc<AtomicU32>,
+    ) {
+        const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+
+        let callback_url = {
+            let cfg = config.read().await;
+            cfg.callback_url.clone()
+        };
+        let Some(callback_url) = callback_url else {
+            return;
+        };
+
+        for attempt in 0..MAX_RECONNECT_ATTEMPTS {
+            if let Some(ref ws) = ws_client {
+                let mut client = ws.lock().await;
+                *client = WebSocketClient::new(callback_url.clone());
+            }
+            if let Some(ref socket) = socket_client {
+                let mut client = socket.lock().await;
+                *client = SocketClient::new(callback_url.clone());
+            }
+
+            if send_message(event.clone(), data.clone(), config, ws_client, socket_client).await.is_ok() {
+                send_failures.store(0, Ordering::Relaxed);
+                eprintln!("回调通道重连成功 (第 {} 次尝试)", attempt + 1);
+                return;
+            }
+
+            tokio::time::sleep(Self::task_retry_backoff(attempt)).await;
+        }
+
+        eprintln!("回调通道连续重连 {} 次均失败，暂时放弃重连", MAX_RECONNECT_ATTEMPTS);
+    }
+
     async fn download_task(
         task: DownloadTask,
         index: usize,
@@ -374,6 +588,9 @@ impl HSDownloader {
         config: Arc<RwLock<DownloadConfig>>,
         ws_client: Option<Arc<Mutex<WebSocketClient>>>,
         socket_client: Option<Arc<Mutex<SocketClient>>>,
+        send_failures: Arc<AtomicU32>,
+        task_downloaders: TaskDownloaders,
+        task_statuses: TaskStatuses,
     ) {
         let total = {
             let cfg = config.read().await;
@@ -394,21 +611,65 @@ impl HSDownloader {
         data.insert("Index".to_string(), serde_json::Value::Number(serde_json::Number::from(index + 1)));
         data.insert("Total".to_string(), serde_json::Value::Number(serde_json::Number::from(total)));
 
-        if let Err(e) = send_message(start_event, data, &config, &ws_client, &socket_client).await {
-            eprintln!("Failed to send start event: {:?}", e);
-        }
+        Self::send_resilient(start_event, data, &config, &ws_client, &socket_client, &send_failures).await;
+
+        let retries = {
+            let cfg = config.read().await;
+            cfg.retries
+        };
+
+        // 任务整体失败（不同于 HTTP 分块级别的重试）时按指数退避重试几次，每次重试
+        // 前发一条 `Msg` 事件方便 UI 显示"重试 2/5"
+        let mut err: Option<Box<dyn std::error::Error + Send + Sync>> = None;
 
-        // 通过工厂函数获取下载器实例（支持多种下载器类型扩展）
-        let err: Option<Box<dyn std::error::Error + Send + Sync>> = {
-            let mut downloader = super::get_downloader::get_downloader(config.clone()).await;
-            match downloader.download(&task).await {
-                Ok(()) => None,
+        // 免锁进度句柄：在第一次尝试前就注册进 `task_statuses`，重试时复用同一个 slot——
+        // `Downloader::download` 每次尝试都会往里重新发布一份 `DownloadStatus`
+        let status_slot: StatusSlot = Arc::new(RwLock::new(None));
+        task_statuses.write().await.insert(task.id.clone(), status_slot.clone());
+
+        for attempt in 0..=retries {
+            if token.is_cancelled() {
+                break;
+            }
+
+            // 通过工厂函数获取下载器实例（支持多种下载器类型扩展），登记进 `task_downloaders`；
+            // 实时进度走上面单独注册的 `status_slot`，不经过这把整段下载期间都被占着的锁
+            let downloader: Arc<Mutex<Box<dyn Downloader>>> =
+                Arc::new(Mutex::new(super::get_downloader::get_downloader(config.clone()).await));
+            task_downloaders.write().await.insert(task.id.clone(), downloader.clone());
+
+            let result = downloader.lock().await.download(&task, token.clone(), status_slot.clone()).await;
+            match result {
+                Ok(()) => {
+                    err = None;
+                    break;
+                }
                 Err(e) => {
-                    eprintln!("下载失败 [{}]: {:?}", task.show_name, e);
-                    Some(e)
+                    eprintln!("下载失败 [{}] (尝试 {}/{}): {:?}", task.show_name, attempt + 1, retries + 1, e);
+                    err = Some(e);
+
+                    // 任务被取消时这次失败只是配合收尾，不是真的需要重试
+                    if attempt == retries || token.is_cancelled() {
+                        break;
+                    }
+
+                    let retry_event = Event {
+                        event_type: EventType::Msg,
+                        name: "重试".to_string(),
+                        show_name: task.show_name.clone(),
+                        id: task.id.clone(),
+                    };
+                    let mut retry_data = HashMap::new();
+                    retry_data.insert("Text".to_string(), serde_json::Value::String(format!("重试 {}/{}", attempt + 1, retries)));
+                    Self::send_resilient(retry_event, retry_data, &config, &ws_client, &socket_client, &send_failures).await;
+
+                    tokio::time::sleep(Self::task_retry_backoff(attempt)).await;
                 }
             }
-        };
+        }
+
+        task_downloaders.write().await.remove(&task.id);
+        task_statuses.write().await.remove(&task.id);
 
         let mut end_data = HashMap::new();
         end_data.insert("URL".to_string(), serde_json::Value::String(task.url));
@@ -416,6 +677,7 @@ impl HSDownloader {
         end_data.insert("ShowName".to_string(), serde_json::Value::String(task.show_name.clone()));
         end_data.insert("Index".to_string(), serde_json::Value::Number(serde_json::Number::from(index + 1)));
         end_data.insert("Total".to_string(), serde_json::Value::Number(serde_json::Number::from(total)));
+        end_data.insert("Cancelled".to_string(), serde_json::Value::Bool(token.is_cancelled()));
 
         if let Some(e) = err {
             if !token.is_cancelled() {
@@ -428,7 +690,7 @@ impl HSDownloader {
                 let mut error_data = HashMap::new();
                 error_data.insert("Error".to_string(), serde_json::Value::String(format!("下载文件失败: {:?}", e)));
 
-                let _ = send_message(error_event, error_data, &config, &ws_client, &socket_client).await;
+                Self::send_resilient(error_event, error_data, &config, &ws_client, &socket_client, &send_failures).await;
             }
         }
 
@@ -439,7 +701,7 @@ impl HSDownloader {
             id: task.id,
         };
 
-        let _ = send_message(end_event, end_data, &config, &ws_client, &socket_client).await;
+        Self::send_resilient(end_event, end_data, &config, &ws_client, &socket_client, &send_failures).await;
     }
 
     pub async fn pause_download(&self) {
@@ -459,9 +721,14 @@ impl HSDownloader {
         let mut data = HashMap::new();
         data.insert("Text".to_string(), serde_json::Value::String("下载已暂停".to_string()));
 
-        let _ = send_message(event, data, &self.config, &self.ws_client, &self.socket_client).await;
+        Self::send_resilient(event, data, &self.config, &self.ws_client, &self.socket_client, &self.send_failures).await;
     }
 
+    /// 重新跑一遍 `start_download`；不需要在这一层自己维护分块续传状态——
+    /// 每个任务重新进 `download_task` 时都会再次经过 `get_downloader` 选出的下载器，
+    /// HTTP 场景下就是 `HTTPDownloader::download`：它会重新 HEAD 探测，用
+    /// `resume_state_matches` 校验 ETag/Last-Modified 没变之后，通过 `load_resume_state`
+    /// 读回上次落盘的 `DownloadChunk` 列表，只重新拉 `done == false` 的分块
     pub async fn resume_download(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         self.start_download().await
     }
@@ -495,10 +762,32 @@ impl HSDownloader {
         Ok(())
     }
 
-    pub async fn get_snapshot(&self, _task_id: &str) -> Option<HashMap<String, serde_json::Value>> {
-        if let Some(monitor) = get_global_monitor().await {
-            return Some(monitor.get_stats().await);
-        }
-        None
+    /// 查询单个任务当前的进度快照，按 [`DownloadTask::id`] 索引；任务还没开始或者已经
+    /// 结束（从 `task_statuses` 里摘除之后）都返回 `None`，而不是退回全局聚合数据——
+    /// 调用方想看某一个任务的进度时，混进全局总量只会误导。走 [`TaskStatuses`] 这张免锁的
+    /// 表而不是 `task_downloaders`：后者那把 `Arc<Mutex<Box<dyn Downloader>>>` 整段下载期间
+    /// 都被 `download_task` 占着，锁到这里只会一直等到下载完成，读不到任何实时进度
+    pub async fn get_snapshot(&self, task_id: &str) -> Option<HashMap<String, serde_json::Value>> {
+        let status_slot = {
+            let task_statuses = self.task_statuses.read().await;
+            task_statuses.get(task_id)?.clone()
+        };
+
+        let Some(monitor) = get_global_monitor().await else {
+            return None;
+        };
+        let stats = monitor.get_stats().await;
+        let current_speed = stats.get("current_speed_bps").and_then(|v| v.as_f64()).unwrap_or(0.0);
+        let average_speed = stats.get("average_speed_bps").and_then(|v| v.as_f64()).unwrap_or(0.0);
+
+        let snapshot = Self::task_snapshot(&status_slot, current_speed, average_speed).await?;
+
+        let mut map = match serde_json::to_value(&snapshot).ok()?.as_object() {
+            Some(obj) => obj.clone(),
+            None => return None,
+        };
+        map.insert("TaskID".to_string(), serde_json::Value::String(task_id.to_string()));
+
+        Some(map.into_iter().collect())
     }
 }
\ No newline at end of file
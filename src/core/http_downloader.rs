@@ -1,18 +1,184 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::fs::OpenOptions;
 use tokio::io::{AsyncSeekExt, AsyncWriteExt};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 use futures::StreamExt;
 use reqwest::{Client, header::{HeaderMap, HeaderValue, RANGE, USER_AGENT, ACCEPT, ACCEPT_LANGUAGE, ACCEPT_ENCODING, CACHE_CONTROL}};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use super::downloader_interface::{Downloader, BaseDownloader};
-use super::downloader::{DownloadTask, DownloadChunk, DownloadConfig, Event, EventType};
+use super::downloader::{DownloadTask, DownloadChunk, DownloadConfig, Event, EventType, ExtractFormat, HashAlgo};
 use super::performance_monitor::PerformanceMonitor;
 use super::send_message::send_message;
 
 const STALL_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// 在同一个任务的所有并发 worker 之间共享的令牌桶限速器，对应 [`DownloadConfig::max_speed_bps`]。
+/// 令牌按 `rate_bps` 随时间匀速补充，每写完一批字节就按本次字节数扣除令牌，余额扣成负数时
+/// `tokio::time::sleep` 到欠下的令牌补回来为止，从而把该任务的总吞吐压到预算以内
+struct TokenBucket {
+    rate_bps: u64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate_bps: u64) -> Self {
+        TokenBucket { rate_bps, tokens: rate_bps as f64, last_refill: Instant::now() }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+        // 令牌桶容量封顶在 1 秒的预算，避免积攒太久后瞬间放行一大波突发流量
+        self.tokens = (self.tokens + elapsed * self.rate_bps as f64).min(self.rate_bps as f64);
+    }
+}
+
+pub struct SharedThrottle {
+    bucket: tokio::sync::Mutex<TokenBucket>,
+}
+
+impl SharedThrottle {
+    fn new(rate_bps: u64) -> Arc<Self> {
+        Arc::new(SharedThrottle { bucket: tokio::sync::Mutex::new(TokenBucket::new(rate_bps)) })
+    }
+
+    /// 为刚写入的 `bytes` 字节扣除令牌，余额不够时先睡到欠下的令牌补回来为止
+    async fn throttle(&self, bytes: i64) {
+        if bytes <= 0 {
+            return;
+        }
+        let wait = {
+            let mut bucket = self.bucket.lock().await;
+            bucket.refill();
+            bucket.tokens -= bytes as f64;
+            if bucket.tokens >= 0.0 {
+                None
+            } else {
+                Some(Duration::from_secs_f64(-bucket.tokens / bucket.rate_bps as f64))
+            }
+        };
+        if let Some(d) = wait {
+            tokio::time::sleep(d).await;
+        }
+    }
+}
+
+/// HEAD 探测得到的远端文件信息，`etag`/`last_modified` 用于断点续传的现场校验，
+/// `supports_ranges` 决定 `download` 走多线程分块路径还是单流顺序路径
+#[derive(Debug, Clone, Default)]
+struct RemoteFileInfo {
+    size: i64,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    supports_ranges: bool,
+}
+
+/// 落盘在 `<save_path>.tthsd-state` 的断点续传状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ResumeState {
+    total_size: i64,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    chunks: Vec<DownloadChunk>,
+}
+
+fn resume_state_path(save_path: &str) -> PathBuf {
+    PathBuf::from(format!("{}.tthsd-state", save_path))
+}
+
+async fn load_resume_state(path: &Path) -> Option<ResumeState> {
+    let bytes = tokio::fs::read(path).await.ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+async fn save_resume_state(path: &Path, state: &ResumeState) {
+    if let Ok(bytes) = serde_json::to_vec(state) {
+        let _ = tokio::fs::write(path, bytes).await;
+    }
+}
+
+/// 下载完成后对产物文件重新过一遍摘要算法，在 `spawn_blocking` 里跑避免大文件顺序
+/// 读取占用 async 运行时线程；即便调用方没有传 `expected_hash` 也会算出来写进快照
+async fn compute_file_hash(path: PathBuf, algo: HashAlgo) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let digest = tokio::task::spawn_blocking(move || -> std::io::Result<String> {
+        let mut file = std::io::BufReader::new(std::fs::File::open(&path)?);
+        let hex = match algo {
+            HashAlgo::Sha256 => {
+                let mut hasher = Sha256::new();
+                std::io::copy(&mut file, &mut hasher)?;
+                format!("{:x}", hasher.finalize())
+            }
+            HashAlgo::Md5 => {
+                let mut hasher = md5::Md5::new();
+                std::io::copy(&mut file, &mut hasher)?;
+                format!("{:x}", hasher.finalize())
+            }
+        };
+        Ok(hex)
+    }).await;
+
+    match digest {
+        Ok(Ok(hex)) => Ok(hex),
+        Ok(Err(e)) => Err(format!("计算摘要失败: {}", e).into()),
+        Err(e) => Err(format!("摘要计算线程 panic: {:?}", e).into()),
+    }
+}
+
+/// 把下载协程通过 `std::sync::mpsc` 推来的字节块适配成同步 `Read`，供 `flate2`/`bzip2`/`lz4`
+/// 的解码器和 `tar::Archive` 在 `spawn_blocking` 里顺序消费
+struct ChannelReader {
+    rx: std::sync::mpsc::Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    fn new(rx: std::sync::mpsc::Receiver<Vec<u8>>) -> Self {
+        ChannelReader { rx, buf: Vec::new(), pos: 0 }
+    }
+}
+
+impl std::io::Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.buf.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.buf = chunk;
+                    self.pos = 0;
+                }
+                // 发送端已经关闭（下载完成或中途出错退出），视为文件结尾
+                Err(_) => return Ok(0),
+            }
+        }
+        let remaining = &self.buf[self.pos..];
+        let n = remaining.len().min(out.len());
+        out[..n].copy_from_slice(&remaining[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// 现场是否仍然有效：ETag（没有则退而比较 Last-Modified）和总大小都得匹配，
+/// 否则服务端内容已经变化，必须丢弃旧状态从头下载
+fn resume_state_matches(state: &ResumeState, remote: &RemoteFileInfo) -> bool {
+    if state.total_size != remote.size {
+        return false;
+    }
+    match (&state.etag, &remote.etag) {
+        (Some(a), Some(b)) => a == b,
+        (None, None) => state.last_modified.is_some() && state.last_modified == remote.last_modified,
+        _ => false,
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadSnapshot {
     #[serde(rename = "downloaded")]
@@ -31,25 +197,50 @@ pub struct DownloadSnapshot {
     pub average_speed_bps: f64,
     #[serde(rename = "elapsed_seconds")]
     pub elapsed_seconds: f64,
+    /// 当前生效的限速预算（字节/秒），对应 [`DownloadConfig::max_speed_bps`]；`None` 表示不限速
+    #[serde(rename = "effective_speed_cap_bps")]
+    pub effective_speed_cap_bps: Option<u64>,
+    /// 下载完成后计算出的文件摘要（十六进制）；未配置 `expected_hash` 时按 SHA-256 计算，
+    /// 未完成或走边下载边解包模式时为 `None`
+    #[serde(rename = "computed_hash")]
+    pub computed_hash: Option<String>,
 }
 
+/// 每个字段要么是 `Copy`，要么已经是 `Arc<RwLock<_>>`，所以 `Clone` 出来的副本
+/// 和原件共享同一份底层状态——这是 [`StatusSlot`] 能脱离 `task_downloaders` 那把大锁
+/// 单独查询实时进度的关键：克隆一份发布到 slot 里，后续任何一边的写入两边都能看见
+#[derive(Clone)]
 pub struct DownloadStatus {
     total_size: i64,
     downloaded: Arc<RwLock<i64>>,
     error_message: Arc<RwLock<Option<String>>>,
     start_time: Instant,
+    speed_cap_bps: Option<u64>,
+    computed_hash: Arc<RwLock<Option<String>>>,
 }
 
+/// 发布单个任务实时进度的免锁句柄：`download_task` 在拿到 `downloader.lock().await`
+/// 之前先建好这个 slot 传给 `Downloader::download`，`HSDownloader::get_snapshot` 只读这个
+/// slot，不需要再去抢那把贯穿整个下载生命周期的 `Arc<Mutex<Box<dyn Downloader>>>`
+pub type StatusSlot = Arc<RwLock<Option<DownloadStatus>>>;
+
 impl DownloadStatus {
-    pub fn new(total_size: i64) -> Self {
+    pub fn new(total_size: i64, speed_cap_bps: Option<u64>) -> Self {
         DownloadStatus {
             total_size,
             downloaded: Arc::new(RwLock::new(0)),
             error_message: Arc::new(RwLock::new(None)),
             start_time: Instant::now(),
+            speed_cap_bps,
+            computed_hash: Arc::new(RwLock::new(None)),
         }
     }
 
+    pub async fn set_computed_hash(&self, hash: String) {
+        let mut h = self.computed_hash.write().await;
+        *h = Some(hash);
+    }
+
     pub async fn set_error(&self, msg: String) {
         let mut error = self.error_message.write().await;
         *error = Some(msg);
@@ -65,6 +256,13 @@ impl DownloadStatus {
         *downloaded += bytes;
     }
 
+    /// 单流模式每次重试都从头拉整个文件，重试前把已统计的进度清零，
+    /// 和 `downloaded_size`（见 [`HTTPDownloader::download_single_stream_with_retry`]）保持一致
+    pub async fn reset_downloaded(&self) {
+        let mut downloaded = self.downloaded.write().await;
+        *downloaded = 0;
+    }
+
     pub async fn get_downloaded(&self) -> i64 {
         let downloaded = self.downloaded.read().await;
         *downloaded
@@ -91,6 +289,8 @@ impl DownloadStatus {
             current_speed_bps: current_speed,
             average_speed_bps: average_speed,
             elapsed_seconds: self.start_time.elapsed().as_secs_f64(),
+            effective_speed_cap_bps: self.speed_cap_bps,
+            computed_hash: self.computed_hash.read().await.clone(),
         }
     }
 }
@@ -128,7 +328,7 @@ impl HTTPDownloader {
         }
     }
 
-    async fn get_file_size(&self, url: &str) -> Result<i64, Box<dyn std::error::Error + Send + Sync>> {
+    async fn get_file_size(&self, url: &str) -> Result<RemoteFileInfo, Box<dyn std::error::Error + Send + Sync>> {
         let response = self.client
             .head(url)
             .send()
@@ -149,7 +349,29 @@ impl HTTPDownloader {
             return Err("Invalid content length".into());
         }
 
-        Ok(content_length)
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string());
+
+        let last_modified = response
+            .headers()
+            .get(reqwest::header::LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string());
+
+        // 没有 Accept-Ranges 头，或显式声明 "none"，都当作不支持 Range 请求；
+        // 否则多个 worker 各自从 0 开始收到完整响应体，会把文件写坏
+        let supports_ranges = response
+            .headers()
+            .get(reqwest::header::ACCEPT_RANGES)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| !s.eq_ignore_ascii_case("none"))
+            .unwrap_or(false)
+            && content_length > 0;
+
+        Ok(RemoteFileInfo { size: content_length, etag, last_modified, supports_ranges })
     }
 
     fn create_chunks(file_size: i64, chunk_size: i64, thread_count: usize) -> Vec<DownloadChunk> {
@@ -172,6 +394,7 @@ impl HTTPDownloader {
                 start_offset: offset,
                 end_offset: end,
                 done: false,
+                bytes_done: 0,
             });
             offset = end + 1;
         }
@@ -179,16 +402,202 @@ impl HTTPDownloader {
         chunks
     }
 
+    /// `task.url` 排在最前，后面跟着 `task.mirrors`；重试时按尝试次数依次滚动选用
+    fn mirror_urls(task: &DownloadTask) -> Vec<String> {
+        let mut urls = vec![task.url.clone()];
+        urls.extend(task.mirrors.iter().cloned());
+        urls
+    }
+
+    /// 指数退避 + 抖动：第 `attempt` 次重试等待 `base_ms * 2^attempt` 左右（上限 2^10 倍），
+    /// 抖动用当前纳秒时间戳取模得到，避免引入额外的随机数依赖
+    fn backoff_with_jitter(base_ms: u64, attempt: u32) -> Duration {
+        let exp_ms = base_ms.saturating_mul(1u64 << attempt.min(10));
+        let bound = exp_ms / 2 + 1;
+        let jitter_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() as u64 % bound)
+            .unwrap_or(0);
+        Duration::from_millis(exp_ms + jitter_ms)
+    }
+
+    async fn send_retry_message(&self, task: &DownloadTask, chunk_index: usize, attempt: u32) {
+        if let Some(ref config) = self.base.config {
+            let event = Event {
+                event_type: EventType::Retry,
+                name: "重试".to_string(),
+                show_name: task.show_name.clone(),
+                id: task.id.clone(),
+            };
+
+            let mut data = serde_json::Map::new();
+            data.insert("ChunkIndex".to_string(), serde_json::Value::Number(chunk_index.into()));
+            data.insert("Attempt".to_string(), serde_json::Value::Number(attempt.into()));
+
+            let _ = send_message(event, data.into_iter().collect(), config, &self.base.ws_client, &self.base.socket_client).await;
+        }
+    }
+
+    /// 一个 worker 的主循环：不断从共享工作队列里抢下一个还没人领的分块索引，下载完
+    /// 就接着抢下一个，队列空了就退出。分块数量固定但并发 worker 数会被
+    /// [`Downloader::download`] 里的吞吐控制器动态增加（只增不减，已经起来的 worker 跑到队列空才退出），
+    /// worker 之间不预先绑定分块。
+    #[allow(clippy::too_many_arguments)]
+    async fn chunk_worker_loop(
+        &self,
+        task: &DownloadTask,
+        queue: Arc<std::sync::Mutex<VecDeque<usize>>>,
+        chunks: Arc<RwLock<Vec<DownloadChunk>>>,
+        downloaded_size: Arc<RwLock<i64>>,
+        resume_state_path: Arc<PathBuf>,
+        remote: Arc<RemoteFileInfo>,
+        max_retries: u32,
+        base_backoff_ms: u64,
+        throttle: Option<Arc<SharedThrottle>>,
+        token: CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        loop {
+            // 只在分块边界上检查取消信号：不会打断正在写的分块，该分块自己的读循环
+            // 也会感知同一个 token，在下一个刷新点提前收尾
+            if token.is_cancelled() {
+                return Ok(());
+            }
+
+            let chunk_index = {
+                let mut q = queue.lock().unwrap();
+                q.pop_front()
+            };
+            let Some(chunk_index) = chunk_index else {
+                return Ok(());
+            };
+
+            self.download_chunk_with_retry(
+                task,
+                chunk_index,
+                chunks.clone(),
+                downloaded_size.clone(),
+                resume_state_path.clone(),
+                remote.clone(),
+                max_retries,
+                base_backoff_ms,
+                throttle.clone(),
+                token.clone(),
+            ).await?;
+        }
+    }
+
+    /// 把一个新 worker 塞进 `join_set`，从 `self` clone 出独立的下载器实例跑在自己的 task 里
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_chunk_worker(
+        &self,
+        join_set: &mut tokio::task::JoinSet<Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+        task: &DownloadTask,
+        queue: &Arc<std::sync::Mutex<VecDeque<usize>>>,
+        chunks: &Arc<RwLock<Vec<DownloadChunk>>>,
+        downloaded_size: &Arc<RwLock<i64>>,
+        resume_state_path: &Arc<PathBuf>,
+        remote: &Arc<RemoteFileInfo>,
+        max_retries: u32,
+        base_backoff_ms: u64,
+        throttle: &Option<Arc<SharedThrottle>>,
+        token: &CancellationToken,
+    ) {
+        let task = task.clone();
+        let queue = queue.clone();
+        let chunks = chunks.clone();
+        let downloaded_size = downloaded_size.clone();
+        let resume_state_path = resume_state_path.clone();
+        let remote = remote.clone();
+        let throttle = throttle.clone();
+        let token = token.clone();
+        let mut downloader = self.clone_downloader();
+        // 共享同一份 `DownloadStatus`，worker 写入的进度才能出现在 status slot 里
+        downloader.status = self.status.clone();
+
+        join_set.spawn(async move {
+            downloader.chunk_worker_loop(
+                &task,
+                queue,
+                chunks,
+                downloaded_size,
+                resume_state_path,
+                remote,
+                max_retries,
+                base_backoff_ms,
+                throttle,
+                token,
+            ).await
+        });
+    }
+
+    /// 在 [`Self::download_chunk`] 外面包一层重试：失败后按退避策略等待、滚动到下一个镜像 URL 重试，
+    /// 由于 `download_chunk` 每次都会从 `chunk.bytes_done` 记录的偏移量继续，所以重试天然是从
+    /// 上一次成功落盘的位置续传，而不是从分块起点重新下载
+    #[allow(clippy::too_many_arguments)]
+    async fn download_chunk_with_retry(
+        &self,
+        task: &DownloadTask,
+        chunk_index: usize,
+        chunks: Arc<RwLock<Vec<DownloadChunk>>>,
+        downloaded_size: Arc<RwLock<i64>>,
+        resume_state_path: Arc<PathBuf>,
+        remote: Arc<RemoteFileInfo>,
+        max_retries: u32,
+        base_backoff_ms: u64,
+        throttle: Option<Arc<SharedThrottle>>,
+        token: CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let urls = Self::mirror_urls(task);
+        let mut last_err: Option<Box<dyn std::error::Error + Send + Sync>> = None;
+
+        for attempt in 0..=max_retries {
+            let mut attempt_task = task.clone();
+            attempt_task.url = urls[attempt as usize % urls.len()].clone();
+
+            match self.download_chunk(&attempt_task, chunk_index, chunks.clone(), downloaded_size.clone(), resume_state_path.clone(), remote.clone(), throttle.clone(), token.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    last_err = Some(e);
+                    // 被取消退出不是真的失败，不需要滚动镜像重试
+                    if attempt == max_retries || token.is_cancelled() {
+                        break;
+                    }
+                    self.send_retry_message(task, chunk_index, attempt + 1).await;
+                    tokio::time::sleep(Self::backoff_with_jitter(base_backoff_ms, attempt)).await;
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| "download_chunk failed".into()))
+    }
+
+    /// 下载单个分块；`chunk_index` 定位到 `chunks` 共享向量里的条目，完成/每个刷新点都会
+    /// 回写该条目的 `done`/`bytes_done`，供 `resume_state_path` 处的断点续传状态落盘使用
+    #[allow(clippy::too_many_arguments)]
     async fn download_chunk(
         &self,
         task: &DownloadTask,
-        chunk: &DownloadChunk,
+        chunk_index: usize,
+        chunks: Arc<RwLock<Vec<DownloadChunk>>>,
         downloaded_size: Arc<RwLock<i64>>,
-        _total_size: i64,
+        resume_state_path: Arc<PathBuf>,
+        remote: Arc<RemoteFileInfo>,
+        throttle: Option<Arc<SharedThrottle>>,
+        token: CancellationToken,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let (start_offset, end_offset, already_done) = {
+            let chunk = &chunks.read().await[chunk_index];
+            (chunk.start_offset + chunk.bytes_done, chunk.end_offset, chunk.start_offset + chunk.bytes_done > chunk.end_offset)
+        };
+
+        // 分块在之前一次运行里已经完整下载过，断点续传时直接跳过
+        if already_done {
+            return Ok(());
+        }
+
         let mut headers = HeaderMap::new();
         headers.insert(USER_AGENT, HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36"));
-        headers.insert(RANGE, HeaderValue::from_str(&format!("bytes={}-{}", chunk.start_offset, chunk.end_offset))?);
+        headers.insert(RANGE, HeaderValue::from_str(&format!("bytes={}-{}", start_offset, end_offset))?);
         headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
         headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en-US,en;q=0.9"));
         headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
@@ -205,10 +614,10 @@ impl HTTPDownloader {
         }
 
         let last_read = Arc::new(RwLock::new(Instant::now()));
-        let stalled_tx = Arc::new(mpsc::channel::<()>(1).0);
+        let stalled = Arc::new(AtomicBool::new(false));
 
         let last_read_clone = last_read.clone();
-        let stalled_tx_clone = stalled_tx.clone();
+        let stalled_clone = stalled.clone();
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(5));
             loop {
@@ -218,7 +627,7 @@ impl HTTPDownloader {
                     lr.elapsed()
                 };
                 if elapsed > STALL_TIMEOUT {
-                    let _ = stalled_tx_clone.send(()).await;
+                    stalled_clone.store(true, Ordering::Relaxed);
                     break;
                 }
             }
@@ -228,7 +637,7 @@ impl HTTPDownloader {
             .write(true)
             .open(&task.save_path).await?;
 
-        writer.seek(std::io::SeekFrom::Start(chunk.start_offset as u64)).await?;
+        writer.seek(std::io::SeekFrom::Start(start_offset as u64)).await?;
 
         const BATCH_UPDATE_THRESHOLD: i64 = 512 * 1024;
         let mut local_downloaded = 0i64;
@@ -245,26 +654,47 @@ impl HTTPDownloader {
 
             writer.write_all(&bytes).await?;
 
+            if let Some(ref throttle) = throttle {
+                throttle.throttle(bytes.len() as i64).await;
+            }
+
             local_downloaded += bytes.len() as i64;
 
             if local_downloaded >= BATCH_UPDATE_THRESHOLD {
-                let mut ds = downloaded_size.write().await;
-                *ds += local_downloaded;
-                drop(ds);
-
-                if let Some(ref monitor) = self.monitor {
-                    monitor.add_bytes(local_downloaded).await;
-                }
-
+                self.flush_chunk_progress(&chunks, chunk_index, local_downloaded, false, &downloaded_size, &resume_state_path, &remote).await;
                 local_downloaded = 0;
             }
 
             // 检查是否停滞
-            if stalled_tx.try_reserve().is_ok() {
+            if stalled.load(Ordering::Relaxed) {
                 return Err("connection stalled".into());
             }
+
+            // 协作式取消：每读到并写完一块字节就检查一次，取消时先把这部分进度刷新再收尾——
+            // 已经读到的字节照常落盘、断点续传状态照常更新，下次恢复时从这里继续
+            if token.is_cancelled() {
+                self.flush_chunk_progress(&chunks, chunk_index, local_downloaded, false, &downloaded_size, &resume_state_path, &remote).await;
+                return Err("download cancelled".into());
+            }
         }
 
+        self.flush_chunk_progress(&chunks, chunk_index, local_downloaded, true, &downloaded_size, &resume_state_path, &remote).await;
+
+        Ok(())
+    }
+
+    /// 把本次读到的字节计入全局计数/监控，并把该分块的最新进度（含是否完成）落盘到断点续传状态
+    #[allow(clippy::too_many_arguments)]
+    async fn flush_chunk_progress(
+        &self,
+        chunks: &Arc<RwLock<Vec<DownloadChunk>>>,
+        chunk_index: usize,
+        local_downloaded: i64,
+        mark_done: bool,
+        downloaded_size: &Arc<RwLock<i64>>,
+        resume_state_path: &Path,
+        remote: &RemoteFileInfo,
+    ) {
         if local_downloaded > 0 {
             let mut ds = downloaded_size.write().await;
             *ds += local_downloaded;
@@ -273,6 +703,278 @@ impl HTTPDownloader {
             if let Some(ref monitor) = self.monitor {
                 monitor.add_bytes(local_downloaded).await;
             }
+
+            if let Some(ref status) = self.status {
+                status.add_downloaded(local_downloaded).await;
+            }
+        }
+
+        let snapshot = {
+            let mut all = chunks.write().await;
+            all[chunk_index].bytes_done += local_downloaded;
+            if mark_done {
+                all[chunk_index].done = true;
+            }
+            all.clone()
+        };
+
+        save_resume_state(resume_state_path, &ResumeState {
+            total_size: remote.size,
+            etag: remote.etag.clone(),
+            last_modified: remote.last_modified.clone(),
+            chunks: snapshot,
+        }).await;
+    }
+
+    /// 服务器不支持 `Range`（或没有声明支持）时走的单流顺序下载路径：只发一次不带
+    /// `Range` 头的 GET，按顺序写入，不预分配文件空间、不能并发分块
+    async fn download_single_stream(
+        &self,
+        task: &DownloadTask,
+        downloaded_size: Arc<RwLock<i64>>,
+        throttle: Option<Arc<SharedThrottle>>,
+        token: CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36"));
+        headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+        headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en-US,en;q=0.9"));
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+        let response = self.client
+            .get(&task.url)
+            .headers(headers)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Bad status: {}", response.status()).into());
+        }
+
+        let last_read = Arc::new(RwLock::new(Instant::now()));
+        let stalled = Arc::new(AtomicBool::new(false));
+
+        let last_read_clone = last_read.clone();
+        let stalled_clone = stalled.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+                let elapsed = {
+                    let lr = last_read_clone.read().await;
+                    lr.elapsed()
+                };
+                if elapsed > STALL_TIMEOUT {
+                    stalled_clone.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        });
+
+        let mut writer = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&task.save_path).await?;
+
+        let mut stream = response.bytes_stream();
+
+        while let Some(bytes_result) = stream.next().await {
+            let bytes = bytes_result?;
+
+            {
+                let mut lr = last_read.write().await;
+                *lr = Instant::now();
+            }
+
+            writer.write_all(&bytes).await?;
+
+            if let Some(ref throttle) = throttle {
+                throttle.throttle(bytes.len() as i64).await;
+            }
+
+            let mut ds = downloaded_size.write().await;
+            *ds += bytes.len() as i64;
+            drop(ds);
+
+            if let Some(ref monitor) = self.monitor {
+                monitor.add_bytes(bytes.len() as i64).await;
+            }
+
+            if let Some(ref status) = self.status {
+                status.add_downloaded(bytes.len() as i64).await;
+            }
+
+            if stalled.load(Ordering::Relaxed) {
+                return Err("connection stalled".into());
+            }
+
+            // 单流模式没有分块概念，已经写盘的字节无需也无法单独"flush"，取消时
+            // 直接在下一个字节到达时收尾即可
+            if token.is_cancelled() {
+                return Err("download cancelled".into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 单流模式没有分块偏移量可以续传，每次重试都只能从头重新请求整个文件
+    #[allow(clippy::too_many_arguments)]
+    async fn download_single_stream_with_retry(
+        &self,
+        task: &DownloadTask,
+        downloaded_size: Arc<RwLock<i64>>,
+        max_retries: u32,
+        base_backoff_ms: u64,
+        throttle: Option<Arc<SharedThrottle>>,
+        token: CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let urls = Self::mirror_urls(task);
+        let mut last_err: Option<Box<dyn std::error::Error + Send + Sync>> = None;
+
+        for attempt in 0..=max_retries {
+            {
+                let mut ds = downloaded_size.write().await;
+                *ds = 0;
+            }
+            if let Some(ref status) = self.status {
+                status.reset_downloaded().await;
+            }
+
+            let mut attempt_task = task.clone();
+            attempt_task.url = urls[attempt as usize % urls.len()].clone();
+
+            match self.download_single_stream(&attempt_task, downloaded_size.clone(), throttle.clone(), token.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    last_err = Some(e);
+                    if attempt == max_retries || token.is_cancelled() {
+                        break;
+                    }
+                    self.send_retry_message(task, 0, attempt + 1).await;
+                    tokio::time::sleep(Self::backoff_with_jitter(base_backoff_ms, attempt)).await;
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| "download_single_stream failed".into()))
+    }
+
+    /// 边下载边解包：请求体必须按顺序喂给解压器，所以强制走单流路径，不能并发分块；
+    /// `task.save_path` 在这个模式下被当作解包的目标目录，而不是单个文件路径
+    async fn download_and_extract(
+        &self,
+        task: &DownloadTask,
+        format: ExtractFormat,
+        downloaded_size: Arc<RwLock<i64>>,
+        token: CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36"));
+        headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+        headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("en-US,en;q=0.9"));
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+        let response = self.client
+            .get(&task.url)
+            .headers(headers)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(format!("Bad status: {}", response.status()).into());
+        }
+
+        // 有界 channel：解压/解包跟不上下载速度时会阻塞发送端，天然起到背压的作用
+        let (tx, rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(32);
+        let dest_dir = task.save_path.clone();
+
+        let extractor = tokio::task::spawn_blocking(move || -> Result<(), String> {
+            let reader = ChannelReader::new(rx);
+            match format {
+                ExtractFormat::Gzip => {
+                    let decoder = flate2::read::GzDecoder::new(reader);
+                    tar::Archive::new(decoder).unpack(&dest_dir).map_err(|e| e.to_string())
+                }
+                ExtractFormat::Bzip2 => {
+                    let decoder = bzip2::read::BzDecoder::new(reader);
+                    tar::Archive::new(decoder).unpack(&dest_dir).map_err(|e| e.to_string())
+                }
+                ExtractFormat::Lz4 => {
+                    let decoder = lz4::Decoder::new(reader).map_err(|e| e.to_string())?;
+                    tar::Archive::new(decoder).unpack(&dest_dir).map_err(|e| e.to_string())
+                }
+            }
+        });
+
+        let mut stream = response.bytes_stream();
+
+        while let Some(bytes_result) = stream.next().await {
+            let bytes = bytes_result?;
+
+            {
+                let mut ds = downloaded_size.write().await;
+                *ds += bytes.len() as i64;
+            }
+            if let Some(ref monitor) = self.monitor {
+                monitor.add_bytes(bytes.len() as i64).await;
+            }
+
+            if let Some(ref status) = self.status {
+                status.add_downloaded(bytes.len() as i64).await;
+            }
+
+            // 解包 worker 提前退出（通常意味着已经出错），停止继续拉流，
+            // 真正的错误原因会在下面 `extractor.await` 里拿到
+            if tx.send(bytes.to_vec()).is_err() {
+                break;
+            }
+
+            if token.is_cancelled() {
+                break;
+            }
+        }
+        drop(tx);
+
+        // 取消时让解包线程随 `tx` 关闭自然收尾（接收到的字节不足会直接报错），
+        // 但最终仍然以"已取消"而不是"解包失败"告知调用方
+        if token.is_cancelled() {
+            let _ = extractor.await;
+            return Err("download cancelled".into());
+        }
+
+        match extractor.await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(format!("解包失败: {}", e).into()),
+            Err(e) => Err(format!("解包线程 panic: {:?}", e).into()),
+        }
+    }
+
+    /// 字节数校验通过后的最后一步：算出产物文件的摘要写进快照；如果任务带了期望值就
+    /// 比对，不一致时删除产物文件并让断点续传状态失效，避免下次运行误判成"已完成"
+    async fn verify_checksum(
+        &self,
+        task: &DownloadTask,
+        resume_state_path: Option<&Path>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let algo = task.expected_hash.as_ref().map(|(a, _)| *a).unwrap_or(HashAlgo::Sha256);
+        let hash = compute_file_hash(PathBuf::from(&task.save_path), algo).await?;
+
+        if let Some(ref status) = self.status {
+            status.set_computed_hash(hash.clone()).await;
+        }
+
+        if let Some((_, expected)) = &task.expected_hash {
+            if !hash.eq_ignore_ascii_case(expected) {
+                let _ = tokio::fs::remove_file(&task.save_path).await;
+                if let Some(path) = resume_state_path {
+                    let _ = tokio::fs::remove_file(path).await;
+                }
+                return Err(format!("校验和不匹配: 期望 {}, 实际 {}", expected, hash).into());
+            }
         }
 
         Ok(())
@@ -313,16 +1015,58 @@ impl Default for BaseDownloader {
 
 #[async_trait::async_trait]
 impl Downloader for HTTPDownloader {
-    async fn download(&mut self, task: &DownloadTask) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let file_size = self.get_file_size(&task.url).await?;
+    async fn download(&mut self, task: &DownloadTask, token: CancellationToken, status_slot: StatusSlot) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let remote = self.get_file_size(&task.url).await?;
+        let file_size = remote.size;
+
+        if token.is_cancelled() {
+            return Err("download cancelled".into());
+        }
+
+        let (max_retries, base_backoff_ms, extract, max_speed_bps) = if let Some(ref config) = self.base.config {
+            let cfg = config.read().await;
+            (cfg.max_retries, cfg.base_backoff_ms, cfg.extract, cfg.max_speed_bps)
+        } else {
+            (3, 500, None, None)
+        };
+
+        self.status = Some(DownloadStatus::new(file_size, max_speed_bps));
+        // 发布一份到 status_slot：底层 `Arc<RwLock<_>>` 字段和 `self.status` 共享，
+        // 调用方从此不需要 `downloader.lock().await` 就能查到实时进度
+        *status_slot.write().await = self.status.clone();
+        let throttle = max_speed_bps.map(SharedThrottle::new);
 
-        self.status = Some(DownloadStatus::new(file_size));
-        
         // 更新全局监控的总大小
         if let Some(ref monitor) = self.monitor {
             monitor.set_total_bytes(file_size);
         }
 
+        if let Some(format) = extract {
+            // 解包需要严格顺序的字节流喂给 tar，强制走单流路径，进度按压缩后的字节数计算
+            let downloaded_size = Arc::new(RwLock::new(0i64));
+            self.download_and_extract(task, format, downloaded_size.clone(), token.clone()).await?;
+
+            let current_size = *downloaded_size.read().await;
+            if current_size != file_size {
+                return Err(format!("download incomplete: {}/{} bytes", current_size, file_size).into());
+            }
+            return Ok(());
+        }
+
+        if !remote.supports_ranges {
+            // 服务器没有声明支持 Range（或显式拒绝）：多线程分块会导致每个 worker 都
+            // 从头收到完整响应体，把文件写坏，所以退化为单流顺序下载
+            let downloaded_size = Arc::new(RwLock::new(0i64));
+            self.download_single_stream_with_retry(task, downloaded_size.clone(), max_retries, base_backoff_ms, throttle.clone(), token.clone()).await?;
+
+            let current_size = *downloaded_size.read().await;
+            if current_size != file_size {
+                return Err(format!("download incomplete: {}/{} bytes", current_size, file_size).into());
+            }
+            self.verify_checksum(task, None).await?;
+            return Ok(());
+        }
+
         let file = OpenOptions::new()
             .write(true)
             .create(true)
@@ -330,7 +1074,7 @@ impl Downloader for HTTPDownloader {
 
         // FAT32 文件系统单文件上限为 4GB，超过时给出明确提示
         const FAT32_MAX_FILE_SIZE: i64 = 4_294_967_295; // 4GB - 1 byte
-        
+
         // 尝试预分配文件大小（提升多线程分块写入性能）
         // 如果失败（例如 FAT32 文件系统不支持大文件），则跳过预分配继续下载
         if let Err(e) = file.set_len(file_size as u64).await {
@@ -357,35 +1101,106 @@ impl Downloader for HTTPDownloader {
             10 * 1024 * 1024
         };
 
-        let chunks = Self::create_chunks(file_size, chunk_size as i64, thread_count);
-        let downloaded_size = Arc::new(RwLock::new(0i64));
+        let resume_state_path = resume_state_path(&task.save_path);
+        let resumed = load_resume_state(&resume_state_path).await.filter(|state| resume_state_matches(state, &remote));
 
-        let mut join_set = tokio::task::JoinSet::new();
+        let chunks = match resumed {
+            Some(state) => state.chunks,
+            None => {
+                let _ = tokio::fs::remove_file(&resume_state_path).await;
+                Self::create_chunks(file_size, chunk_size as i64, thread_count)
+            }
+        };
 
-        for chunk in chunks {
-            let task_clone = task.clone();
-            let downloaded_size_clone = downloaded_size.clone();
-            let self_clone = self.clone_downloader();
+        let initial_downloaded: i64 = chunks.iter().map(|c| c.bytes_done).sum();
+        if initial_downloaded > 0 {
+            if let Some(ref monitor) = self.monitor {
+                monitor.add_bytes(initial_downloaded).await;
+            }
+        }
 
-            join_set.spawn(async move {
-                self_clone.download_chunk(&task_clone, &chunk, downloaded_size_clone, file_size).await
-            });
+        let downloaded_size = Arc::new(RwLock::new(initial_downloaded));
+        let chunks = Arc::new(RwLock::new(chunks));
+        let resume_state_path = Arc::new(resume_state_path);
+        let remote = Arc::new(remote);
+        let chunk_count = chunks.read().await.len();
+
+        // 还没人领的分块索引放进共享队列，worker 数量不再跟分块数一一绑定：
+        // 起步只开 `INITIAL_WORKERS` 个保守的 worker，下面的控制器按 `PerformanceMonitor`
+        // 实测吞吐逐步加到 `worker_ceiling`（`thread_count`），吞吐不再增长就停止再加
+        let work_queue: Arc<std::sync::Mutex<VecDeque<usize>>> =
+            Arc::new(std::sync::Mutex::new((0..chunk_count).collect()));
+
+        const INITIAL_WORKERS: usize = 2;
+        const GROWTH_CHECK_INTERVAL: Duration = Duration::from_millis(800);
+        // 本次采样吞吐比上次至少高出这个比例才算"还在爬升"，否则视为进入平台期
+        const GROWTH_THRESHOLD: f64 = 1.05;
+
+        let worker_ceiling = thread_count.clamp(1, chunk_count.max(1));
+        let mut join_set = tokio::task::JoinSet::new();
+        let mut workers_spawned = 0usize;
+
+        for _ in 0..INITIAL_WORKERS.min(worker_ceiling) {
+            self.spawn_chunk_worker(
+                &mut join_set, task, &work_queue, &chunks, &downloaded_size,
+                &resume_state_path, &remote, max_retries, base_backoff_ms, &throttle, &token,
+            );
+            workers_spawned += 1;
         }
 
-        while let Some(result) = join_set.join_next().await {
-            if let Err(e) = result {
-                self.send_error_message(format!("worker error: {:?}", e)).await;
-                if let Some(ref status) = self.status {
-                    status.set_error(format!("worker error: {:?}", e)).await;
+        let mut last_speed_sample = 0.0f64;
+        let mut growth_check = tokio::time::interval(GROWTH_CHECK_INTERVAL);
+
+        while !join_set.is_empty() {
+            tokio::select! {
+                Some(result) = join_set.join_next() => {
+                    if let Err(e) = result {
+                        self.send_error_message(format!("worker error: {:?}", e)).await;
+                        if let Some(ref status) = self.status {
+                            status.set_error(format!("worker error: {:?}", e)).await;
+                        }
+                    }
+                }
+                _ = growth_check.tick() => {
+                    let queue_has_work = !work_queue.lock().unwrap().is_empty();
+                    if workers_spawned < worker_ceiling && queue_has_work && !token.is_cancelled() {
+                        let current_speed = if let Some(ref monitor) = self.monitor {
+                            let stats = monitor.get_stats().await;
+                            stats.get("current_speed_bps").and_then(|v| v.as_f64()).unwrap_or(0.0)
+                        } else {
+                            0.0
+                        };
+
+                        if workers_spawned == 0 || current_speed >= last_speed_sample * GROWTH_THRESHOLD {
+                            self.spawn_chunk_worker(
+                                &mut join_set, task, &work_queue, &chunks, &downloaded_size,
+                                &resume_state_path, &remote, max_retries, base_backoff_ms, &throttle, &token,
+                            );
+                            workers_spawned += 1;
+                        }
+                        last_speed_sample = current_speed;
+                    }
                 }
             }
         }
 
+        // 每个 worker 自己在 `chunk_worker_loop`/`download_chunk` 里感知取消信号，会在把当前
+        // 分块已读到的字节落盘+更新断点续传状态后主动退出，不等该分块读完；所有 worker 收尾、`join_set` 清空后
+        // 上面的 `while` 循环自然结束，这里只需要看 token 是否被取消来决定返回值
+        if token.is_cancelled() {
+            return Err("download cancelled".into());
+        }
+
         let current_size = *downloaded_size.read().await;
         if current_size != file_size {
             return Err(format!("download incomplete: {}/{} bytes", current_size, file_size).into());
         }
 
+        self.verify_checksum(task, Some(resume_state_path.as_path())).await?;
+
+        // 下载成功完成，断点续传状态不再需要
+        let _ = tokio::fs::remove_file(resume_state_path.as_path()).await;
+
         Ok(())
     }
 